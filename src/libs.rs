@@ -1,7 +1,22 @@
 pub mod checkbox;
+pub mod config;
+pub mod date;
+pub mod dir_rules;
 pub mod errors;
+pub mod file_type;
+pub mod filters;
 pub mod fs;
+pub mod journal;
 pub mod keywords;
+pub mod matcher;
+pub mod normalize;
+pub mod options;
 pub mod parse_args;
+pub mod plan;
+pub mod prompt;
+pub mod rule;
+pub mod segment;
+pub mod size;
 pub mod stdout;
+pub mod stopwords;
 pub mod tui;
@@ -0,0 +1,394 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::libs::errors::Error;
+use crate::libs::file_type::Category;
+use crate::libs::options::{
+    ByExtension, DateSource, LinkKind, MatchMode, Noise, NormalForm, OnConflict, Priority,
+    Segmentation, Strategy, TransferMode,
+};
+
+const APP_NAME: &str = "group_files_into_dirs";
+const CONFIG_FILENAME: &str = "config.toml";
+
+/// Grouping settings of a profile, the same as the command line flags.
+/// Unset values are taken from the next source, flags then the profile then defaults.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    pub keywords: Option<String>,
+    pub levels: Option<Vec<String>>,
+    pub dir_as_keyword: Option<bool>,
+    pub verbose: Option<bool>,
+    pub on_conflict: Option<OnConflict>,
+    pub mode: Option<TransferMode>,
+    pub keep_going: Option<bool>,
+    pub recursive: Option<bool>,
+    pub max_depth: Option<usize>,
+    pub prune_empty: Option<bool>,
+    pub include_hidden: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub skip_symlinks: Option<bool>,
+    pub non_utf8_names: Option<bool>,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    #[serde(rename = "match")]
+    pub match_mode: Option<MatchMode>,
+    pub normalize: Option<NormalForm>,
+    pub fold_width: Option<bool>,
+    pub fold_kana: Option<bool>,
+    pub segment: Option<Segmentation>,
+    pub strategy: Option<Vec<Strategy>>,
+    pub hide: Option<Vec<Noise>>,
+    pub stopwords_file: Option<PathBuf>,
+    pub priority: Option<Priority>,
+    pub all_matches: Option<LinkKind>,
+    pub by_extension: Option<ByExtension>,
+    pub sniff: Option<bool>,
+    pub by_date: Option<String>,
+    pub date_source: Option<Vec<DateSource>>,
+    pub undated_dir: Option<String>,
+    pub by_size: Option<String>,
+}
+
+impl Settings {
+    // `-k`, `--level`, `-d` and `--by-*` choose how files are grouped.
+    fn chooses_mode(&self) -> bool {
+        self.keywords.is_some()
+            || self.levels.is_some()
+            || self.dir_as_keyword == Some(true)
+            || self.by_extension.is_some()
+            || self.by_date.is_some()
+            || self.by_size.is_some()
+    }
+
+    // values of `self` which would win over the mode or symlink handling chosen in `other`.
+    fn without_overridden(self, other: &Settings) -> Settings {
+        let settings = match other.chooses_mode() {
+            true => Settings {
+                keywords: None,
+                levels: None,
+                dir_as_keyword: None,
+                by_extension: None,
+                by_date: None,
+                by_size: None,
+                ..self
+            },
+            false => self,
+        };
+        Settings {
+            follow_symlinks: settings
+                .follow_symlinks
+                .filter(|_| other.skip_symlinks != Some(true)),
+            skip_symlinks: settings
+                .skip_symlinks
+                .filter(|_| other.follow_symlinks != Some(true)),
+            ..settings
+        }
+    }
+
+    /// Values set in `other` replace the ones in `self`.
+    /// A grouping mode or symlink handling set in `other` replaces the one in `self`.
+    pub fn merge(self, other: Settings) -> Settings {
+        let base = self.without_overridden(&other);
+        Settings {
+            keywords: other.keywords.or(base.keywords),
+            levels: other.levels.or(base.levels),
+            dir_as_keyword: other.dir_as_keyword.or(base.dir_as_keyword),
+            verbose: other.verbose.or(base.verbose),
+            on_conflict: other.on_conflict.or(base.on_conflict),
+            mode: other.mode.or(base.mode),
+            keep_going: other.keep_going.or(base.keep_going),
+            recursive: other.recursive.or(base.recursive),
+            max_depth: other.max_depth.or(base.max_depth),
+            prune_empty: other.prune_empty.or(base.prune_empty),
+            include_hidden: other.include_hidden.or(base.include_hidden),
+            follow_symlinks: other.follow_symlinks.or(base.follow_symlinks),
+            skip_symlinks: other.skip_symlinks.or(base.skip_symlinks),
+            non_utf8_names: other.non_utf8_names.or(base.non_utf8_names),
+            include: other.include.or(base.include),
+            exclude: other.exclude.or(base.exclude),
+            match_mode: other.match_mode.or(base.match_mode),
+            normalize: other.normalize.or(base.normalize),
+            fold_width: other.fold_width.or(base.fold_width),
+            fold_kana: other.fold_kana.or(base.fold_kana),
+            segment: other.segment.or(base.segment),
+            strategy: other.strategy.or(base.strategy),
+            hide: other.hide.or(base.hide),
+            stopwords_file: other.stopwords_file.or(base.stopwords_file),
+            priority: other.priority.or(base.priority),
+            all_matches: other.all_matches.or(base.all_matches),
+            by_extension: other.by_extension.or(base.by_extension),
+            sniff: other.sniff.or(base.sniff),
+            by_date: other.by_date.or(base.by_date),
+            date_source: other.date_source.or(base.date_source),
+            undated_dir: other.undated_dir.or(base.undated_dir),
+            by_size: other.by_size.or(base.by_size),
+        }
+    }
+
+    /// Fills unset values with defaults, except ones without a value like `max_depth`.
+    pub fn resolved(self) -> Settings {
+        Settings {
+            dir_as_keyword: Some(self.dir_as_keyword.unwrap_or_default()),
+            verbose: Some(self.verbose.unwrap_or_default()),
+            on_conflict: Some(self.on_conflict.unwrap_or_default()),
+            mode: Some(self.mode.unwrap_or_default()),
+            keep_going: Some(self.keep_going.unwrap_or_default()),
+            recursive: Some(self.recursive.unwrap_or_default() || self.max_depth.is_some()),
+            prune_empty: Some(self.prune_empty.unwrap_or_default()),
+            include_hidden: Some(self.include_hidden.unwrap_or_default()),
+            follow_symlinks: Some(self.follow_symlinks.unwrap_or_default()),
+            skip_symlinks: Some(self.skip_symlinks.unwrap_or_default()),
+            non_utf8_names: Some(self.non_utf8_names.unwrap_or_default()),
+            include: Some(self.include.unwrap_or_default()),
+            exclude: Some(self.exclude.unwrap_or_default()),
+            match_mode: Some(self.match_mode.unwrap_or_default()),
+            normalize: Some(self.normalize.unwrap_or_default()),
+            fold_width: Some(self.fold_width.unwrap_or_default()),
+            fold_kana: Some(self.fold_kana.unwrap_or_default()),
+            segment: Some(self.segment.unwrap_or_default()),
+            strategy: Some(self.strategy.unwrap_or(vec![Strategy::Delimiters])),
+            hide: Some(self.hide.unwrap_or_default()),
+            priority: Some(self.priority.unwrap_or_default()),
+            sniff: Some(self.sniff.unwrap_or_default()),
+            date_source: Some(self.date_source.unwrap_or(vec![DateSource::Mtime])),
+            ..self
+        }
+    }
+}
+
+/// The config file, flags given on the command line take precedence.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// directory names and the keywords grouped into them.
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// directory names replacing the built-in ones of `--by-extension=category`.
+    pub categories: BTreeMap<Category, String>,
+    /// settings chosen with `--profile`.
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+impl Config {
+    /// Settings of the profile on top of the top level ones.
+    pub fn settings(&self, profile: Option<&str>) -> Result<Settings, Error> {
+        let settings = Settings {
+            include: (!self.include.is_empty()).then(|| self.include.clone()),
+            exclude: (!self.exclude.is_empty()).then(|| self.exclude.clone()),
+            ..Default::default()
+        };
+        let Some(profile) = profile else {
+            return Ok(settings);
+        };
+
+        match self.profiles.get(profile) {
+            Some(profile) => Ok(settings.merge(profile.clone())),
+            None => Err(Error::ConfigError(format!(
+                "no profile named {}, profiles are: {}",
+                profile,
+                self.profiles
+                    .keys()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/group_files_into_dirs/config.toml`, falling back to `~/.config`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .or_else(|| env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_dir.join(APP_NAME).join(CONFIG_FILENAME))
+}
+
+pub fn parse_config(content: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(content)
+}
+
+/// Loads `path`, or the default config file when it is `None`.
+/// A missing default config file is not an error.
+pub fn load_config(path: Option<&Path>) -> Result<Config, Error> {
+    let (path, required) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match default_config_path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound && !required => return Ok(Config::default()),
+        Err(e) => {
+            return Err(Error::ConfigError(format!("{}: {}", path.display(), e)));
+        }
+    };
+
+    parse_config(&content)
+        .map_err(|e| Error::ConfigError(format!("{}: {}", path.display(), e.message())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(
+            r#"
+include = ["*.pdf"]
+exclude = ["*.part", "*.crdownload"]
+
+[aliases]
+Invoices = ["inv", "invoice", "請求書"]
+
+[categories]
+images = "Pictures"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.include, vec!["*.pdf"]);
+        assert_eq!(config.exclude, vec!["*.part", "*.crdownload"]);
+        assert_eq!(config.aliases["Invoices"], vec!["inv", "invoice", "請求書"]);
+        assert_eq!(config.categories[&Category::Images], "Pictures");
+
+        assert_eq!(parse_config("").unwrap(), Config::default());
+        assert!(parse_config("unknown = 1").is_err());
+        assert!(parse_config("include = \"*.pdf\"").is_err());
+        assert!(parse_config("[categories]\nfonts = \"Fonts\"").is_err());
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let config = parse_config(
+            r#"
+exclude = ["*.part"]
+
+[profiles.downloads]
+keywords = "invoice,quote"
+mode = "copy"
+on-conflict = "rename"
+match = "substring"
+include = ["*.pdf"]
+"#,
+        )
+        .unwrap();
+
+        let settings = config.settings(Some("downloads")).unwrap();
+        assert_eq!(settings.keywords, Some("invoice,quote".to_string()));
+        assert_eq!(settings.mode, Some(TransferMode::Copy));
+        assert_eq!(settings.on_conflict, Some(OnConflict::Rename));
+        assert_eq!(settings.match_mode, Some(MatchMode::Substring));
+        assert_eq!(settings.include, Some(vec!["*.pdf".to_string()]));
+        assert_eq!(settings.exclude, Some(vec!["*.part".to_string()]));
+
+        let command_line = Settings {
+            mode: Some(TransferMode::Move),
+            ..Default::default()
+        };
+        let settings = settings.merge(command_line).resolved();
+        assert_eq!(settings.mode, Some(TransferMode::Move));
+        assert_eq!(settings.on_conflict, Some(OnConflict::Rename));
+        assert_eq!(settings.priority, Some(Priority::Order));
+        assert_eq!(settings.max_depth, None);
+
+        assert_eq!(config.settings(None).unwrap().keywords, None);
+        assert!(matches!(
+            config.settings(Some("unknown")),
+            Err(Error::ConfigError(_))
+        ));
+        assert!(parse_config("[profiles.downloads]\nmode = \"teleport\"").is_err());
+    }
+
+    #[test]
+    fn test_command_line_mode_replaces_profile_mode() {
+        let config = parse_config(
+            r#"
+[profiles.quotes]
+keywords = "quote"
+follow-symlinks = true
+
+[profiles.extensions]
+by-extension = "category"
+skip-symlinks = true
+"#,
+        )
+        .unwrap();
+
+        let command_line = Settings {
+            dir_as_keyword: Some(true),
+            skip_symlinks: Some(true),
+            ..Default::default()
+        };
+        let settings = config
+            .settings(Some("quotes"))
+            .unwrap()
+            .merge(command_line)
+            .resolved();
+        assert_eq!(settings.keywords, None);
+        assert_eq!(settings.dir_as_keyword, Some(true));
+        assert_eq!(settings.follow_symlinks, Some(false));
+        assert_eq!(settings.skip_symlinks, Some(true));
+
+        let command_line = Settings {
+            keywords: Some("quote".to_string()),
+            follow_symlinks: Some(true),
+            ..Default::default()
+        };
+        let settings = config
+            .settings(Some("extensions"))
+            .unwrap()
+            .merge(command_line)
+            .resolved();
+        assert_eq!(settings.by_extension, None);
+        assert_eq!(settings.keywords, Some("quote".to_string()));
+        assert_eq!(settings.follow_symlinks, Some(true));
+        assert_eq!(settings.skip_symlinks, Some(false));
+
+        // other flags keep the mode of the profile.
+        let command_line = Settings {
+            verbose: Some(true),
+            ..Default::default()
+        };
+        let settings = config
+            .settings(Some("extensions"))
+            .unwrap()
+            .merge(command_line);
+        assert_eq!(settings.by_extension, Some(ByExtension::Category));
+        assert_eq!(settings.skip_symlinks, Some(true));
+    }
+
+    #[test]
+    fn test_load_config() {
+        let tmpdir = std::env::temp_dir().join("test_load_config");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        let path = tmpdir.join(CONFIG_FILENAME);
+        assert!(matches!(
+            load_config(Some(&path)),
+            Err(Error::ConfigError(_))
+        ));
+
+        fs::write(&path, "exclude = [\"*.part\"]\n").unwrap();
+        let config = load_config(Some(&path)).unwrap();
+        assert_eq!(config.exclude, vec!["*.part"]);
+    }
+}
@@ -0,0 +1,266 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use regex::Regex;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::SystemTime;
+
+use crate::libs::errors::Error;
+use crate::libs::options::DateSource;
+use crate::libs::rule::is_relative_dir;
+
+// digits next to the date would make it a different number.
+static FULL_DATE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\D)((?:19|20)\d{2})[-_./]?(\d{2})[-_./]?(\d{2})(?:\D|$)").unwrap()
+});
+static YEAR_MONTH_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\D)((?:19|20)\d{2})[-_.](\d{2})(?:\D|$)").unwrap());
+
+const PLACEHOLDERS: [&str; 7] = ["yyyy", "yy", "mm", "m", "dd", "d", "q"];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Placeholder(String),
+}
+
+/// A directory name template of `--by-date`, e.g. `{yyyy}/{mm}` or `{yyyy}-Q{q}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DateTemplate {
+    parts: Vec<Part>,
+}
+
+fn invalid(template: &str, message: &str) -> Error {
+    Error::InvalidDateTemplateError(format!("{}: {}", template, message))
+}
+
+impl DateTemplate {
+    pub fn parse(template: &str) -> Result<DateTemplate, Error> {
+        let mut parts = vec![];
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                return Err(invalid(template, "unclosed `{`"));
+            };
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let placeholder = &rest[start + 1..start + end];
+            if !PLACEHOLDERS.contains(&placeholder) {
+                return Err(invalid(
+                    template,
+                    &format!(
+                        "unknown placeholder `{{{}}}`, use one of {}",
+                        placeholder,
+                        PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(" ")
+                    ),
+                ));
+            }
+            parts.push(Part::Placeholder(placeholder.to_string()));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        if !parts
+            .iter()
+            .any(|part| matches!(part, Part::Placeholder(_)))
+        {
+            return Err(invalid(template, "no placeholder"));
+        }
+        if !is_relative_dir(template) {
+            return Err(invalid(template, "directory must be a relative path"));
+        }
+
+        Ok(DateTemplate { parts })
+    }
+
+    /// The directory, relative to the target path, for files of `date`.
+    pub fn render(&self, date: NaiveDate) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.to_string(),
+                Part::Placeholder(placeholder) => match placeholder.as_str() {
+                    "yyyy" => format!("{:04}", date.year()),
+                    "yy" => format!("{:02}", date.year() % 100),
+                    "mm" => format!("{:02}", date.month()),
+                    "m" => date.month().to_string(),
+                    "dd" => format!("{:02}", date.day()),
+                    "d" => date.day().to_string(),
+                    _ => (date.month0() / 3 + 1).to_string(),
+                },
+            })
+            .collect()
+    }
+}
+
+fn local_date(time: SystemTime) -> NaiveDate {
+    DateTime::<Local>::from(time).date_naive()
+}
+
+#[cfg(unix)]
+fn changed(metadata: &fs::Metadata) -> Option<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let time = DateTime::from_timestamp(metadata.ctime(), 0)?;
+    Some(time.into())
+}
+
+#[cfg(not(unix))]
+fn changed(_metadata: &fs::Metadata) -> Option<SystemTime> {
+    None
+}
+
+// when the photo was taken, or when the file was last changed by the camera.
+fn exif_date(path: &Path) -> Option<NaiveDate> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+
+    [
+        (exif::Tag::DateTimeOriginal, exif::In::PRIMARY),
+        (exif::Tag::DateTime, exif::In::PRIMARY),
+    ]
+    .iter()
+    .filter_map(|(tag, ifd)| exif.get_field(*tag, *ifd))
+    .find_map(|field| match &field.value {
+        exif::Value::Ascii(values) => {
+            let datetime = exif::DateTime::from_ascii(values.first()?).ok()?;
+            NaiveDate::from_ymd_opt(
+                datetime.year.into(),
+                datetime.month.into(),
+                datetime.day.into(),
+            )
+        }
+        _ => None,
+    })
+}
+
+/// The first valid date in the file name, `2021-01-31`, `20210131` or `2021-01`.
+pub fn filename_date(filename: &str) -> Option<NaiveDate> {
+    let full_dates = FULL_DATE_REGEX.captures_iter(filename).map(|c| {
+        let day = c[3].parse().ok()?;
+        NaiveDate::from_ymd_opt(c[1].parse().ok()?, c[2].parse().ok()?, day)
+    });
+    let year_months = YEAR_MONTH_REGEX
+        .captures_iter(filename)
+        .map(|c| NaiveDate::from_ymd_opt(c[1].parse().ok()?, c[2].parse().ok()?, 1));
+
+    full_dates.chain(year_months).flatten().next()
+}
+
+/// The date of the file at `path` from the first of `sources` which has one.
+pub fn file_date(path: &Path, sources: &[DateSource]) -> Option<NaiveDate> {
+    sources.iter().find_map(|source| match source {
+        DateSource::Mtime => fs::metadata(path).ok()?.modified().ok().map(local_date),
+        DateSource::Ctime => {
+            let metadata = fs::metadata(path).ok()?;
+            metadata
+                .created()
+                .ok()
+                .or_else(|| changed(&metadata))
+                .map(local_date)
+        }
+        DateSource::Exif => exif_date(path),
+        DateSource::Filename => filename_date(&path.file_name()?.to_string_lossy()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_date_template() {
+        let template = DateTemplate::parse("{yyyy}/{mm}").unwrap();
+        assert_eq!(template.render(date(2021, 1, 31)), "2021/01");
+
+        let template = DateTemplate::parse("{yyyy}-Q{q}").unwrap();
+        assert_eq!(template.render(date(2021, 1, 31)), "2021-Q1");
+        assert_eq!(template.render(date(2021, 12, 1)), "2021-Q4");
+
+        let template = DateTemplate::parse("{yy}.{m}.{d} ({dd})").unwrap();
+        assert_eq!(template.render(date(2005, 3, 4)), "05.3.4 (04)");
+
+        let error = |template| DateTemplate::parse(template).unwrap_err().to_string();
+        assert!(error("{yyyy}/{month}").contains("unknown placeholder `{month}`"));
+        assert!(error("{yyyy").contains("unclosed"));
+        assert!(error("photos").contains("no placeholder"));
+        assert!(error("../{yyyy}").contains("relative path"));
+        assert!(error("/{yyyy}").contains("relative path"));
+    }
+
+    #[test]
+    fn test_filename_date() {
+        assert_eq!(
+            filename_date("inquiry_2021-01-31.txt"),
+            Some(date(2021, 1, 31))
+        );
+        assert_eq!(
+            filename_date("IMG_20190704_1200.jpg"),
+            Some(date(2019, 7, 4))
+        );
+        assert_eq!(filename_date("invoice_2021-02.pdf"), Some(date(2021, 2, 1)));
+        assert_eq!(
+            filename_date("2021.12.24 party.jpg"),
+            Some(date(2021, 12, 24))
+        );
+        assert_eq!(filename_date("report_2021-13-01.pdf"), None);
+        assert_eq!(filename_date("order_120210131.pdf"), None);
+        assert_eq!(filename_date("notes.txt"), None);
+    }
+
+    // a JPEG holding only an EXIF DateTime field.
+    fn jpeg_with_exif_date(datetime: &[u8; 19]) -> Vec<u8> {
+        let mut tiff = b"II*\x00\x08\x00\x00\x00".to_vec();
+        tiff.extend(b"\x01\x00\x32\x01\x02\x00\x14\x00\x00\x00\x1a\x00\x00\x00\x00\x00\x00\x00");
+        tiff.extend(datetime);
+        tiff.push(0);
+
+        let mut jpeg = b"\xff\xd8\xff\xe1".to_vec();
+        jpeg.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend(b"Exif\x00\x00");
+        jpeg.extend(tiff);
+        jpeg.extend(b"\xff\xd9");
+        jpeg
+    }
+
+    #[test]
+    fn test_file_date() {
+        let tmpdir = std::env::temp_dir().join("test_file_date");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        let photo = tmpdir.join("photo_2020-05-05.jpg");
+        fs::write(&photo, jpeg_with_exif_date(b"2019:08:09 10:11:12")).unwrap();
+        let notes = tmpdir.join("notes.txt");
+        fs::write(&notes, b"").unwrap();
+
+        assert_eq!(
+            file_date(&photo, &[DateSource::Exif]),
+            Some(date(2019, 8, 9))
+        );
+        assert_eq!(
+            file_date(&photo, &[DateSource::Filename, DateSource::Exif]),
+            Some(date(2020, 5, 5))
+        );
+        assert_eq!(
+            file_date(&notes, &[DateSource::Exif, DateSource::Filename]),
+            None
+        );
+        assert_eq!(
+            file_date(&notes, &[DateSource::Exif, DateSource::Mtime]),
+            Some(local_date(
+                fs::metadata(&notes).unwrap().modified().unwrap()
+            ))
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use toml::Spanned;
+
+use crate::libs::errors::Error;
+use crate::libs::filters::Filters;
+use crate::libs::matcher::Matcher;
+
+/// File in a directory listing what routes files into it in dir-as-keyword mode.
+pub const RULES_FILENAME: &str = ".group_rules.toml";
+/// Older file listing more keywords of a directory, one per line, like `keywords` of the rule file.
+pub const ALIASES_FILENAME: &str = ".group_aliases";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RuleFile {
+    keywords: Vec<String>,
+    regexes: Vec<Spanned<String>>,
+    extensions: Vec<String>,
+    exclude: Vec<Spanned<String>>,
+}
+
+/// What routes files into a directory besides its name.
+#[derive(Clone, Debug, Default)]
+pub struct DirRules {
+    pub keywords: Vec<String>,
+    pub regexes: Vec<Regex>,
+    /// lowercase, without the dot.
+    pub extensions: Vec<String>,
+    /// files matching these globs are never routed into the directory.
+    pub exclude: Filters,
+}
+
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+fn rule_file_error(path: &Path, content: &str, offset: usize, message: impl ToString) -> Error {
+    Error::RuleFileError(format!(
+        "{}:{}: {}",
+        path.display(),
+        line_of(content, offset),
+        message.to_string()
+    ))
+}
+
+/// Parses the rule file at `path`, errors name the file and the line.
+pub fn parse_dir_rules(path: &Path, content: &str) -> Result<DirRules, Error> {
+    let rule_file: RuleFile = toml::from_str(content).map_err(|e| {
+        let offset = e.span().map_or(0, |span| span.start);
+        rule_file_error(path, content, offset, e.message())
+    })?;
+
+    let regexes = rule_file
+        .regexes
+        .iter()
+        .map(|pattern| {
+            RegexBuilder::new(pattern.get_ref())
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| rule_file_error(path, content, pattern.span().start, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for pattern in rule_file.exclude.iter() {
+        Filters::new(&[], &[pattern.get_ref().to_string()])
+            .map_err(|e| rule_file_error(path, content, pattern.span().start, e))?;
+    }
+    let exclude = rule_file
+        .exclude
+        .into_iter()
+        .map(|pattern| pattern.into_inner())
+        .collect::<Vec<_>>();
+
+    Ok(DirRules {
+        keywords: rule_file.keywords,
+        regexes,
+        extensions: rule_file
+            .extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect(),
+        exclude: Filters::new(&[], &exclude)?,
+    })
+}
+
+// keywords of the aliases file, one per line.
+fn load_aliases(dir: &Path) -> Result<Vec<String>, Error> {
+    let path = dir.join(ALIASES_FILENAME);
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(Error::RuleFileError(format!("{}: {}", path.display(), e))),
+    }
+}
+
+/// Loads the rule file of `dir`, keywords of its aliases file are added to the rule file ones.
+/// A directory without either has no rules.
+pub fn load_dir_rules(dir: &Path) -> Result<DirRules, Error> {
+    let path = dir.join(RULES_FILENAME);
+    let mut rules = match fs::read_to_string(&path) {
+        Ok(content) => parse_dir_rules(&path, &content)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => DirRules::default(),
+        Err(e) => return Err(Error::RuleFileError(format!("{}: {}", path.display(), e))),
+    };
+    rules.keywords.extend(load_aliases(dir)?);
+    Ok(rules)
+}
+
+impl DirRules {
+    /// Length of the longest keyword, regex match or extension routing the file here,
+    /// `keywords` are the ones taken from the directory name.
+    pub fn match_len(&self, keywords: &[String], path: &Path, matcher: &Matcher) -> Option<usize> {
+        if self.exclude.reject(path).is_some() {
+            return None;
+        }
+        let name = path.file_name()?.to_string_lossy();
+
+        let keyword_lens = keywords
+            .iter()
+            .chain(self.keywords.iter())
+            .filter(|keyword| keyword.len() > 1 && matcher.is_match(keyword, &name))
+            .map(|keyword| keyword.chars().count());
+        let normalized = matcher.normalize(&name);
+        let regex_lens = self
+            .regexes
+            .iter()
+            .filter_map(|re| re.find(&normalized))
+            .map(|m| m.as_str().chars().count());
+        let extension_lens = Path::new(name.as_ref())
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .filter(|ext| self.extensions.contains(ext))
+            .map(|ext| ext.chars().count());
+
+        keyword_lens.chain(regex_lens).chain(extension_lens).max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_rules() {
+        let path = Path::new(RULES_FILENAME);
+        let rules = parse_dir_rules(
+            path,
+            r#"
+keywords = ["inv"]
+regexes = ['^\d{4}-\d{2}_bill']
+extensions = [".PDF"]
+exclude = ["*draft*"]
+"#,
+        )
+        .unwrap();
+        let keywords = vec!["Invoices".to_string()];
+        let match_len = |name| rules.match_len(&keywords, Path::new(name), &Matcher::default());
+
+        assert_eq!(match_len("invoices_1.txt"), Some(8));
+        assert_eq!(match_len("inv_1.txt"), Some(3));
+        assert_eq!(match_len("2021-01_bill.txt"), Some(12));
+        assert_eq!(match_len("scan.pdf"), Some(3));
+        assert_eq!(match_len("inv_draft.txt"), None);
+        assert_eq!(match_len("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_dir_rules_errors() {
+        let path = Path::new("Invoices").join(RULES_FILENAME);
+        let error = |content| parse_dir_rules(&path, content).unwrap_err().to_string();
+
+        assert_eq!(
+            error("keywords = [\"inv\"]\nregexes = ['(']\n"),
+            format!(
+                "rule file error: {}:2: regex parse error:\n    (\n    ^\nerror: unclosed group",
+                path.display()
+            )
+        );
+        assert!(error("keywords = [\"inv\"]\n\nexclude = \"*.tmp\"\n")
+            .starts_with(&format!("rule file error: {}:3: ", path.display())));
+        assert!(error("keywords = [\"inv\"]\nunknown = 1\n")
+            .starts_with(&format!("rule file error: {}:2: ", path.display())));
+        assert!(error("exclude = ['[a']")
+            .starts_with(&format!("rule file error: {}:1: ", path.display())));
+    }
+}
@@ -1,5 +1,6 @@
 // use anyhow::Error as AnyError;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum Error {
     #[error("move file error: {0}")]
@@ -8,8 +9,28 @@ pub enum Error {
     KeywordLengthError(String),
     #[error("io error: {0}")]
     IOError(String),
+    #[error("prompt error: {0}")]
+    PromptError(String),
+    #[error("undo error: {0}")]
+    UndoError(String),
+    #[error("{0} files could not be moved")]
+    PartialFailure(usize),
     #[error("no keywords found")]
     NoKeywordsFound,
+    #[error("invalid glob pattern: {0}")]
+    InvalidGlobError(String),
+    #[error("invalid regex keyword: {0}")]
+    InvalidRegexError(String),
+    #[error("invalid alias: {0}")]
+    InvalidAliasError(String),
+    #[error("rule file error: {0}")]
+    RuleFileError(String),
+    #[error("config error: {0}")]
+    ConfigError(String),
+    #[error("invalid date template: {0}")]
+    InvalidDateTemplateError(String),
+    #[error("invalid size bucket: {0}")]
+    InvalidSizeBucketError(String),
 }
 
 impl From<std::io::Error> for Error {
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::libs::keywords::extract_extension;
+use crate::libs::options::ByExtension;
+
+/// A kind of file grouped together by `--by-extension=category`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    Images,
+    Documents,
+    Archives,
+    Audio,
+    Video,
+}
+
+const CATEGORIES: [(Category, &[&str]); 5] = [
+    (
+        Category::Images,
+        &[
+            "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "svg",
+            "ico", "psd", "raw", "cr2", "nef", "arw", "dng",
+        ],
+    ),
+    (
+        Category::Documents,
+        &[
+            "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "rtf", "txt",
+            "md", "csv", "tsv", "epub", "pages", "numbers", "key",
+        ],
+    ),
+    (
+        Category::Archives,
+        &[
+            "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "lzh", "dmg", "iso",
+        ],
+    ),
+    (
+        Category::Audio,
+        &[
+            "mp3", "wav", "flac", "aac", "m4a", "ogg", "opus", "wma", "aif", "aiff", "mid", "midi",
+        ],
+    ),
+    (
+        Category::Video,
+        &[
+            "mp4", "m4v", "mov", "avi", "mkv", "webm", "wmv", "flv", "mpg", "mpeg", "3gp",
+        ],
+    ),
+];
+
+impl Category {
+    pub fn all() -> impl Iterator<Item = Category> {
+        CATEGORIES.iter().map(|(category, _)| *category)
+    }
+
+    /// `ext` is lowercase, without the dot.
+    pub fn from_extension(ext: &str) -> Option<Category> {
+        CATEGORIES
+            .iter()
+            .find(|(_, extensions)| extensions.contains(&ext))
+            .map(|(category, _)| *category)
+    }
+
+    /// The directory the category is grouped into, `names` come from the config file.
+    pub fn dir(&self, names: &BTreeMap<Category, String>) -> String {
+        if let Some(name) = names.get(self) {
+            return name.to_string();
+        }
+        match self {
+            Category::Images => "images",
+            Category::Documents => "documents",
+            Category::Archives => "archives",
+            Category::Audio => "audio",
+            Category::Video => "video",
+        }
+        .to_string()
+    }
+}
+
+const SNIFF_LEN: usize = 16;
+
+// byte strings at offsets in the head of a file, and the extension they stand for.
+type Signature = (&'static [(usize, &'static [u8])], &'static str);
+
+// more specific signatures come first.
+const SIGNATURES: &[Signature] = &[
+    (&[(0, b"\x89PNG\r\n\x1a\n")], "png"),
+    (&[(0, b"\xff\xd8\xff")], "jpg"),
+    (&[(0, b"GIF87a")], "gif"),
+    (&[(0, b"GIF89a")], "gif"),
+    (&[(0, b"II*\x00")], "tif"),
+    (&[(0, b"MM\x00*")], "tif"),
+    (&[(0, b"RIFF"), (8, b"WEBP")], "webp"),
+    (&[(4, b"ftypheic")], "heic"),
+    (&[(0, b"%PDF-")], "pdf"),
+    (&[(0, b"PK\x03\x04")], "zip"),
+    (&[(0, b"\x1f\x8b")], "gz"),
+    (&[(0, b"BZh")], "bz2"),
+    (&[(0, b"\xfd7zXZ\x00")], "xz"),
+    (&[(0, b"7z\xbc\xaf\x27\x1c")], "7z"),
+    (&[(0, b"Rar!\x1a\x07")], "rar"),
+    (&[(0, b"ID3")], "mp3"),
+    (&[(0, b"OggS")], "ogg"),
+    (&[(0, b"fLaC")], "flac"),
+    (&[(0, b"RIFF"), (8, b"WAVE")], "wav"),
+    (&[(4, b"ftypM4A")], "m4a"),
+    (&[(0, b"RIFF"), (8, b"AVI ")], "avi"),
+    (&[(4, b"ftyp")], "mp4"),
+    (&[(0, b"\x1a\x45\xdf\xa3")], "mkv"),
+];
+
+/// Guesses the extension of the file at `path` from its first bytes.
+pub fn sniff_extension(path: &Path) -> Option<&'static str> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)
+        .ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)
+        .ok()?;
+
+    SIGNATURES
+        .iter()
+        .find(|(parts, _)| {
+            parts
+                .iter()
+                .all(|(offset, magic)| head.get(*offset..offset + magic.len()) == Some(*magic))
+        })
+        .map(|(_, ext)| *ext)
+}
+
+/// The directory `--by-extension` groups the file at `path` into, relative to the target path.
+/// With `sniff`, files without a known extension are told apart by their contents.
+pub fn extension_dir(
+    path: &Path,
+    by: ByExtension,
+    sniff: bool,
+    names: &BTreeMap<Category, String>,
+) -> Option<String> {
+    let ext = path
+        .file_name()
+        .and_then(|name| extract_extension(&name.to_string_lossy()));
+    let sniffed = || sniff.then(|| sniff_extension(path)).flatten();
+
+    match by {
+        ByExtension::Extension => ext.or_else(|| sniffed().map(|ext| ext.to_string())),
+        ByExtension::Category => ext
+            .and_then(|ext| Category::from_extension(&ext))
+            .or_else(|| sniffed().and_then(Category::from_extension))
+            .map(|category| category.dir(names)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_extension_dir() {
+        let tmpdir = std::env::temp_dir().join("test_extension_dir");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::write(tmpdir.join("scan"), b"%PDF-1.7\n").unwrap();
+        fs::write(tmpdir.join("photo.bin"), b"\x89PNG\r\n\x1a\n\x00\x00").unwrap();
+        fs::write(tmpdir.join("notes"), b"just text").unwrap();
+        fs::write(tmpdir.join("Song.MP3"), b"").unwrap();
+
+        let names = BTreeMap::from([(Category::Audio, "Music".to_string())]);
+        let dir = |name, by, sniff| extension_dir(&tmpdir.join(name), by, sniff, &names);
+
+        assert_eq!(
+            dir("Song.MP3", ByExtension::Extension, false),
+            Some("mp3".to_string())
+        );
+        assert_eq!(dir("scan", ByExtension::Extension, false), None);
+        assert_eq!(
+            dir("scan", ByExtension::Extension, true),
+            Some("pdf".to_string())
+        );
+        assert_eq!(
+            dir("Song.MP3", ByExtension::Category, false),
+            Some("Music".to_string())
+        );
+        assert_eq!(dir("photo.bin", ByExtension::Category, false), None);
+        assert_eq!(
+            dir("photo.bin", ByExtension::Category, true),
+            Some("images".to_string())
+        );
+        assert_eq!(dir("notes", ByExtension::Category, true), None);
+    }
+
+    #[test]
+    fn test_sniff_extension() {
+        let tmpdir = std::env::temp_dir().join("test_sniff_extension");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        let sniff = |head: &[u8]| {
+            let path = tmpdir.join("file");
+            fs::write(&path, head).unwrap();
+            sniff_extension(&path)
+        };
+        assert_eq!(sniff(b"RIFF\x00\x00\x00\x00WAVEfmt "), Some("wav"));
+        assert_eq!(sniff(b"RIFF\x00\x00\x00\x00WEBPVP8 "), Some("webp"));
+        assert_eq!(sniff(b"\x00\x00\x00\x20ftypM4A \x00"), Some("m4a"));
+        assert_eq!(sniff(b"\x00\x00\x00\x20ftypisom\x00"), Some("mp4"));
+        assert_eq!(sniff(b"PK\x03\x04"), Some("zip"));
+        assert_eq!(sniff(b"RI"), None);
+        assert_eq!(sniff(b""), None);
+    }
+}
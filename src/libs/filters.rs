@@ -0,0 +1,96 @@
+use glob::{MatchOptions, Pattern};
+use std::path::Path;
+
+use crate::libs::errors::Error;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Glob patterns deciding which of the scanned files are grouped.
+#[derive(Clone, Debug, Default)]
+pub struct Filters {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<Pattern>, Error> {
+    patterns
+        .iter()
+        .map(|p| Pattern::new(p).map_err(|e| Error::InvalidGlobError(format!("{}: {}", p, e))))
+        .collect()
+}
+
+// patterns with a separator are matched against the whole relative path.
+fn matches(pattern: &Pattern, relative_path: &Path) -> bool {
+    if pattern.as_str().contains('/') {
+        return pattern.matches_path_with(relative_path, MATCH_OPTIONS);
+    }
+    relative_path
+        .file_name()
+        .is_some_and(|name| pattern.matches_with(&name.to_string_lossy(), MATCH_OPTIONS))
+}
+
+impl Filters {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Filters, Error> {
+        Ok(Filters {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// `None` when the file is considered, otherwise why it is filtered out.
+    pub fn reject(&self, relative_path: &Path) -> Option<&'static str> {
+        if !self.include.is_empty() && !self.include.iter().any(|p| matches(p, relative_path)) {
+            return Some("not included");
+        }
+        if self.exclude.iter().any(|p| matches(p, relative_path)) {
+            return Some("excluded");
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        let filters = Filters::new(&strings(&["*.pdf", "docs/*"]), &strings(&["*draft*"])).unwrap();
+
+        assert_eq!(filters.reject(Path::new("invoice.pdf")), None);
+        assert_eq!(filters.reject(Path::new("sub/INVOICE.PDF")), None);
+        assert_eq!(filters.reject(Path::new("docs/notes.txt")), None);
+        assert_eq!(
+            filters.reject(Path::new("docs/deep/notes.txt")),
+            Some("not included")
+        );
+        assert_eq!(filters.reject(Path::new("notes.txt")), Some("not included"));
+        assert_eq!(
+            filters.reject(Path::new("invoice_draft.pdf")),
+            Some("excluded")
+        );
+
+        let filters = Filters::new(&[], &strings(&["*.part", "*.crdownload"])).unwrap();
+        assert_eq!(filters.reject(Path::new("movie.mp4")), None);
+        assert_eq!(
+            filters.reject(Path::new("movie.mp4.part")),
+            Some("excluded")
+        );
+    }
+
+    #[test]
+    fn test_filters_invalid_glob() {
+        assert!(matches!(
+            Filters::new(&strings(&["[a"]), &[]),
+            Err(Error::InvalidGlobError(_))
+        ));
+    }
+}
@@ -1,25 +1,35 @@
-use std::fs;
+use spinners::{Spinner, Spinners};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, FileTimes};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use crate::libs::errors::Error;
+use crate::libs::journal::{Journal, JOURNAL_FILENAME, REPLACED_DIRNAME};
+use crate::libs::options::{OnConflict, Options, Priority, Symlinks, TransferMode};
+use crate::libs::plan::{
+    Ambiguity, Candidate, Conflict, Failure, Plan, Report, SkipReason, Transfer,
+};
+use crate::libs::prompt;
+use crate::libs::rule::Rule;
 use crate::libs::stdout::*;
 
 pub fn parse_path(mut path: String) -> Result<PathBuf, Error> {
-    if cfg!(windows) {
-        if path.ends_with("\"") {
-            path = path.trim_end_matches("\"").to_string();
-        }
+    if cfg!(windows) && path.ends_with("\"") {
+        path = path.trim_end_matches("\"").to_string();
     }
 
     if !Path::new(&path).exists() {
         return Err(Error::IOError(format!("path {} does not exist", path)));
     }
 
-    return Ok(Path::new(&path).to_path_buf());
+    Ok(Path::new(&path).to_path_buf())
 }
 
 pub fn mkdir_for_keyword(keyword: String, basepath: &PathBuf) -> Result<String, Error> {
-    if !Path::exists(&basepath) {
+    if !Path::exists(basepath) {
         return Err(Error::IOError(format!(
             "path {} is not exists",
             basepath.display()
@@ -35,85 +45,884 @@ pub fn mkdir_for_keyword(keyword: String, basepath: &PathBuf) -> Result<String,
     Ok(keyword)
 }
 
-pub fn files_in_dir(path: &PathBuf) -> Result<Vec<String>, Error> {
-    let files = fs::read_dir(path)?
-        .filter_map(|e| {
-            if let Ok(entry) = e {
-                let metadata = entry.metadata().ok()?;
-                let filename = entry.file_name().into_string().ok()?;
+// a symlink is checked by itself, so a dangling one has a time too.
+fn modified(path: &Path) -> Result<SystemTime, Error> {
+    Ok(fs::symlink_metadata(path)?.modified()?)
+}
 
-                if filename.starts_with(".") || metadata.is_dir() {
-                    return None;
-                }
+pub fn file_hash(path: &Path) -> std::io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.write(&buf[..len]);
+    }
+    Ok(hasher.finish())
+}
 
-                return Some(filename);
-            }
-            None
-        })
-        .collect();
+// compares the bytes, the source is removed when they are the same.
+fn same_contents(a: &Path, b: &Path) -> Result<bool, Error> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
 
-    Ok(files)
+    let mut a = fs::File::open(a)?;
+    let mut b = fs::File::open(b)?;
+    let mut buf_a = [0; 64 * 1024];
+    let mut buf_b = [0; 64 * 1024];
+    loop {
+        let len = a.read(&mut buf_a)?;
+        if len == 0 {
+            return Ok(true);
+        }
+        // both files have the same length.
+        b.read_exact(&mut buf_b[..len])?;
+        if buf_a[..len] != buf_b[..len] {
+            return Ok(false);
+        }
+    }
 }
 
-pub fn move_files_to_dir(
-    basepath: &PathBuf,
-    filenames: &Vec<String>,
-    keywords: &Vec<String>,
-    verbose: bool,
-) -> Result<Vec<String>, Error> {
-    let mut moved_files = vec![];
+/// `file.txt` → `file (1).txt`, counting up until `taken` returns false.
+pub fn numbered_path(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !taken(candidate))
+        .unwrap()
+}
+
+// a dangling symlink is also an existing file.
+fn path_exists(path: &Path) -> bool {
+    path.symlink_metadata().is_ok()
+}
+
+/// Adds a transfer to the plan, an existing destination is resolved with `options.on_conflict`.
+/// Returns whether the file is going to be transferred.
+pub fn plan_transfer(
+    plan: &mut Plan,
+    filename: &str,
+    src: PathBuf,
+    dst: PathBuf,
+    options: &Options,
+) -> Result<bool, Error> {
+    // files could be moved by other keywords.
+    if plan.has_src(&src) {
+        plan.skip(filename, SkipReason::AlreadyMoved);
+        return Ok(false);
+    }
 
-    for filename in filenames {
-        let lower_filename = filename.to_lowercase();
-        for keyword in keywords {
-            // if filename is the same as keyword, it is a directory so skip it.
-            if filename == keyword {
+    if !path_exists(&dst) && !plan.has_dst(&dst) {
+        plan.add_transfer(filename, src, dst);
+        return Ok(true);
+    }
+
+    // destination file is already exists.
+    let conflict = match options.on_conflict {
+        OnConflict::Rename => Conflict::Rename,
+        // another file of this run takes the destination.
+        _ if plan.has_dst(&dst) => {
+            plan.skip(filename, SkipReason::AlreadyExists);
+            return Ok(false);
+        }
+        OnConflict::Skip => {
+            plan.skip(filename, SkipReason::AlreadyExists);
+            return Ok(false);
+        }
+        OnConflict::Overwrite => Conflict::Overwrite,
+        OnConflict::Newer if modified(&src)? > modified(&dst)? => Conflict::Overwrite,
+        OnConflict::Newer => {
+            plan.skip(filename, SkipReason::NotNewer);
+            return Ok(false);
+        }
+        // the source is kept in place unless files are moved.
+        OnConflict::Identical
+            if options.mode == TransferMode::Move && same_contents(&src, &dst)? =>
+        {
+            Conflict::Duplicate
+        }
+        OnConflict::Identical => {
+            plan.skip(filename, SkipReason::AlreadyExists);
+            return Ok(false);
+        }
+        OnConflict::Prompt => Conflict::Prompt,
+    };
+
+    let dst = if conflict == Conflict::Rename {
+        numbered_path(&dst, |p| path_exists(p) || plan.has_dst(p))
+    } else {
+        dst
+    };
+    plan.add_conflicting_transfer(filename, src, dst, conflict);
+
+    Ok(true)
+}
+
+/// Collects files under `path` as paths relative to it, subdirectories are scanned with
+/// `options.recursive`. Directories directly under `path` named in `skip_dirs` are not scanned.
+pub fn files_in_dir(
+    path: &Path,
+    options: &Options,
+    skip_dirs: &[String],
+) -> Result<Vec<PathBuf>, Error> {
+    let files = files_with_metadata_in_dir(path, options, skip_dirs)?;
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// `files_in_dir` along with the metadata read while scanning,
+/// of the link target when symlinks are followed.
+pub fn files_with_metadata_in_dir(
+    path: &Path,
+    options: &Options,
+    skip_dirs: &[String],
+) -> Result<Vec<(PathBuf, fs::Metadata)>, Error> {
+    let max_depth = if options.recursive {
+        options.max_depth
+    } else {
+        Some(1)
+    };
+    let report = |path: &Path, reason: &str| {
+        if options.verbose {
+            skipped_entry(path.display().to_string(), reason.to_string());
+        }
+    };
+
+    let mut files = vec![];
+    // followed symlinks could make a loop.
+    let mut visited_dirs = HashSet::new();
+    let mut dirs = vec![(PathBuf::new(), 1)];
+
+    while let Some((relative_dir, depth)) = dirs.pop() {
+        for entry in fs::read_dir(path.join(&relative_dir))?.flatten() {
+            let relative_path = relative_dir.join(entry.file_name());
+            let filename = entry.file_name().to_string_lossy().to_string();
+
+            if entry.file_name().to_str().is_none() && !options.non_utf8_names {
+                report(&relative_path, "not a UTF-8 name");
+                continue;
+            }
+            // the journal of this run is open while files are moved, replaced files are kept for it.
+            if depth == 1 && (filename == JOURNAL_FILENAME || filename == REPLACED_DIRNAME) {
+                continue;
+            }
+            if filename.starts_with(".") && !options.include_hidden {
+                report(&relative_path, "hidden");
+                continue;
+            }
+            let Ok(mut metadata) = entry.metadata() else {
+                report(&relative_path, "metadata cannot be read");
                 continue;
+            };
+            if metadata.is_symlink() {
+                match options.symlinks {
+                    Symlinks::Skip => {
+                        report(&relative_path, "symlink");
+                        continue;
+                    }
+                    Symlinks::Follow => match fs::metadata(entry.path()) {
+                        Ok(target) => metadata = target,
+                        Err(_) => {
+                            report(&relative_path, "broken symlink");
+                            continue;
+                        }
+                    },
+                    // the link itself is grouped.
+                    Symlinks::AsFiles => {}
+                }
             }
-            let lower_keyword = keyword.to_lowercase();
-            if lower_filename.contains(&lower_keyword) {
-                let src = &basepath.join(filename);
-                // files could be moved by other keywords.
-                if !src.exists() {
-                    already_moved(filename.to_string());
+
+            if metadata.is_dir() {
+                // files should not bounce between keyword directories.
+                if depth == 1 && skip_dirs.contains(&filename) {
+                    report(&relative_path, "keyword directory");
                     continue;
                 }
-                // create a new directory for the keyword.
-                let dirname = mkdir_for_keyword(keyword.to_string(), basepath)?;
+                if max_depth.is_none_or(|max_depth| depth < max_depth)
+                    && fs::canonicalize(entry.path()).is_ok_and(|dir| visited_dirs.insert(dir))
+                {
+                    dirs.push((relative_path, depth + 1));
+                }
+                continue;
+            }
+            files.push((relative_path, metadata));
+        }
+    }
+
+    Ok(files)
+}
+
+/// The last component of a path returned by `files_in_dir`, used for matching.
+pub fn file_name(relative_path: &Path) -> String {
+    relative_path
+        .file_name()
+        .unwrap_or(relative_path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
 
-                let dst = &basepath.join(dirname).join(filename);
-                // destination file is already exists.
-                if dst.exists() {
+/// Drops files rejected by the `--include` and `--exclude` globs.
+pub fn filter_files<P: AsRef<Path>>(filenames: &[P], options: &Options) -> Vec<PathBuf> {
+    filenames
+        .iter()
+        .map(|path| path.as_ref())
+        .filter(|path| match options.filters.reject(path) {
+            Some(reason) => {
+                if options.verbose {
+                    skipped_entry(path.display().to_string(), reason.to_string());
+                }
+                false
+            }
+            None => true,
+        })
+        .map(|path| path.to_path_buf())
+        .collect()
+}
+
+/// Plans grouping `files` into the directories `dirname_for` returns, relative to `basepath`.
+/// Files it returns `None` for, and files already in their directory, are left in place.
+pub fn plan_grouping<P: AsRef<Path>>(
+    basepath: &Path,
+    files: &[P],
+    options: &Options,
+    mut dirname_for: impl FnMut(&Path) -> Option<String>,
+) -> Result<Plan, Error> {
+    let mut plan = Plan::default();
+
+    for path in filter_files(files, options) {
+        let Some(dirname) = dirname_for(&path) else {
+            continue;
+        };
+        // the file is already grouped.
+        if path.parent() == Some(Path::new(&dirname)) {
+            continue;
+        }
+
+        let filename = path.to_string_lossy();
+        let src = basepath.join(&path);
+        let dst = basepath.join(&dirname).join(path.file_name().unwrap());
+        if plan_transfer(&mut plan, &filename, src, dst, options)? {
+            plan_dirs(&mut plan, basepath, &dirname);
+        }
+    }
+
+    plan_pruning(basepath, &mut plan, options);
+
+    Ok(plan)
+}
+
+/// Finds source subdirectories which become empty when the planned files are moved,
+/// with `--prune-empty` only.
+pub fn plan_pruning(basepath: &Path, plan: &mut Plan, options: &Options) {
+    if !options.prune_empty || options.mode != TransferMode::Move {
+        return;
+    }
+    let moved = plan
+        .transfers
+        .iter()
+        .map(|t| t.src.clone())
+        .collect::<HashSet<_>>();
+
+    let mut candidates = moved
+        .iter()
+        .flat_map(|src| src.ancestors().skip(1))
+        .filter(|dir| dir.starts_with(basepath) && *dir != basepath)
+        .map(|dir| dir.to_path_buf())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+
+    let mut pruned_dirs: Vec<PathBuf> = vec![];
+    for dir in candidates {
+        let emptied = fs::read_dir(&dir)
+            .map(|mut entries| {
+                entries.all(|e| {
+                    e.is_ok_and(|e| moved.contains(&e.path()) || pruned_dirs.contains(&e.path()))
+                })
+            })
+            .unwrap_or(false);
+        if emptied {
+            pruned_dirs.push(dir);
+        }
+    }
+
+    plan.pruned_dirs = pruned_dirs;
+}
+
+/// Adds the directory and its missing parents to the plan, parents first.
+pub fn plan_dirs(plan: &mut Plan, basepath: &Path, dirname: &str) {
+    let mut dirs = Path::new(dirname).ancestors().collect::<Vec<_>>();
+    dirs.reverse();
+    for dir in dirs.into_iter().filter(|d| !d.as_os_str().is_empty()) {
+        if !basepath.join(dir).exists() {
+            plan.add_dir(&dir.to_string_lossy());
+        }
+    }
+}
+
+/// With `options.all_matches`, links the file transferred from `src` into the other
+/// matching directories.
+pub fn plan_links(
+    plan: &mut Plan,
+    basepath: &Path,
+    filename: &str,
+    src: &Path,
+    chosen: &str,
+    dirs: &[String],
+    options: &Options,
+) {
+    let Some(link_kind) = options.all_matches else {
+        return;
+    };
+    let Some(name) = src.file_name() else {
+        return;
+    };
+
+    for dir in dirs.iter().filter(|dir| *dir != chosen) {
+        let dst = basepath.join(dir).join(name);
+        if path_exists(&dst) || plan.has_dst(&dst) {
+            plan.skip(filename, SkipReason::AlreadyExists);
+            continue;
+        }
+        plan_dirs(plan, basepath, dir);
+        plan.add_link(filename, src.to_path_buf(), dst, link_kind.mode());
+    }
+}
+
+/// Number of files each directory was matched by.
+pub fn count_matched_files<'a>(
+    candidates: impl Iterator<Item = &'a [Candidate]>,
+) -> HashMap<String, usize> {
+    candidates
+        .flatten()
+        .fold(HashMap::new(), |mut acc, candidate| {
+            *acc.entry(candidate.dir.clone()).or_insert(0) += 1;
+            acc
+        })
+}
+
+const SKIP_ANSWER: &str = "(skip)";
+
+/// Picks the directory for a file matching several keywords with `options.priority`,
+/// the choice is recorded in the plan. `candidates` are in the order keywords were given.
+pub fn choose_dir(
+    plan: &mut Plan,
+    filename: &str,
+    candidates: &[Candidate],
+    matched_files: &HashMap<String, usize>,
+    options: &Options,
+) -> Result<Option<String>, Error> {
+    if candidates.len() < 2 {
+        return Ok(candidates.first().map(|c| c.dir.clone()));
+    }
+
+    let dirs = candidates.iter().map(|c| c.dir.clone()).collect::<Vec<_>>();
+    let chosen = match options.priority {
+        Priority::Order => Some(dirs[0].clone()),
+        // the earlier keyword wins a tie.
+        Priority::Longest => candidates
+            .iter()
+            .rev()
+            .max_by_key(|c| c.keyword_len)
+            .map(|c| c.dir.clone()),
+        Priority::Specific => candidates
+            .iter()
+            .min_by_key(|c| matched_files.get(&c.dir).copied().unwrap_or(0))
+            .map(|c| c.dir.clone()),
+        Priority::Ask if options.dry_run => None,
+        Priority::Ask => {
+            let mut items = dirs.iter().map(|d| d.as_str()).collect::<Vec<_>>();
+            items.push(SKIP_ANSWER);
+            let answer = prompt::select(format!("{} matches several keywords", filename), &items)?;
+            (answer != SKIP_ANSWER).then_some(answer)
+        }
+    };
+
+    plan.ambiguous.push(Ambiguity {
+        filename: filename.to_string(),
+        dirs,
+        chosen: chosen.clone(),
+    });
+
+    Ok(chosen)
+}
+
+// directories each rule of a level would group the file into.
+fn level_candidates(rules: &[Rule], path: &Path, options: &Options) -> Vec<Candidate> {
+    let filename = path.to_string_lossy();
+    let name = file_name(path);
+    let matcher = options.matcher();
+    let mut candidates: Vec<Candidate> = vec![];
+    for rule in rules {
+        // if filename is the same as keyword, it is a directory so skip it.
+        if *rule == Rule::Keyword(filename.to_string()) {
+            continue;
+        }
+        let Some(candidate) = rule.candidate(&name, &matcher) else {
+            continue;
+        };
+        if !candidates.iter().any(|c| c.dir == candidate.dir) {
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
+// every nested directory made of one candidate from each level.
+fn nested_dirs(levels: &[Vec<Candidate>]) -> Vec<String> {
+    levels
+        .iter()
+        .fold(vec![String::new()], |parents, candidates| {
+            parents
+                .iter()
+                .flat_map(|parent| {
+                    candidates
+                        .iter()
+                        .map(move |c| format!("{}{}/", parent, c.dir))
+                })
+                .collect()
+        })
+        .into_iter()
+        .map(|dir| dir.trim_end_matches('/').to_string())
+        .collect()
+}
+
+/// Plans grouping into nested directories, one level of keywords per directory level.
+/// A file goes as deep as consecutive levels match, it is not grouped unless the first does.
+pub fn plan_moves<P: AsRef<Path>>(
+    basepath: &Path,
+    filenames: &[P],
+    levels: &[Vec<Rule>],
+    options: &Options,
+) -> Result<Plan, Error> {
+    let mut plan = Plan::default();
+
+    let matches = filenames
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let candidates = levels
+                .iter()
+                .map(|rules| level_candidates(rules, path, options))
+                .take_while(|candidates| !candidates.is_empty())
+                .collect::<Vec<_>>();
+            (path, candidates)
+        })
+        .collect::<Vec<_>>();
+    let matched_files = (0..levels.len())
+        .map(|level| {
+            count_matched_files(
+                matches
+                    .iter()
+                    .filter_map(|(_, candidates)| candidates.get(level))
+                    .map(|c| c.as_slice()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for (path, candidates) in matches {
+        if candidates.is_empty() {
+            continue;
+        }
+        let filename = path.to_string_lossy();
+        let dirs = nested_dirs(&candidates);
+        // the file is already grouped.
+        if path
+            .parent()
+            .is_some_and(|parent| dirs.iter().any(|dir| Path::new(dir) == parent))
+        {
+            continue;
+        }
+
+        let mut chosen = vec![];
+        for (level, candidates) in candidates.iter().enumerate() {
+            match choose_dir(
+                &mut plan,
+                &filename,
+                candidates,
+                &matched_files[level],
+                options,
+            )? {
+                Some(dir) => chosen.push(dir),
+                None => break,
+            }
+        }
+        if chosen.is_empty() {
+            continue;
+        }
+        let dirname = chosen.join("/");
+
+        let src = basepath.join(path);
+        let dst = basepath.join(&dirname).join(path.file_name().unwrap());
+        if plan_transfer(&mut plan, &filename, src.clone(), dst, options)? {
+            plan_dirs(&mut plan, basepath, &dirname);
+            plan_links(
+                &mut plan, basepath, &filename, &src, &dirname, &dirs, options,
+            );
+        }
+    }
+
+    plan_pruning(basepath, &mut plan, options);
+
+    Ok(plan)
+}
+
+// asks what to do with an existing destination, `None` means skip.
+// a renamed file must not take the destination of another file of the plan.
+fn resolve_by_prompt(filename: &str, dst: &Path, plan: &Plan) -> Result<Option<PathBuf>, Error> {
+    let answer = prompt::select(
+        format!("{} already exists in {}", filename, dst.display()),
+        &["skip", "overwrite", "rename"],
+    )?;
+
+    Ok(match answer.as_str() {
+        "overwrite" => Some(dst.to_path_buf()),
+        "rename" => Some(numbered_path(dst, |p| path_exists(p) || plan.has_dst(p))),
+        _ => None,
+    })
+}
+
+#[cfg(unix)]
+fn symlink_file(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink_file(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+// files larger than this show a spinner while copied across filesystems.
+const LARGE_FILE_LEN: u64 = 64 * 1024 * 1024;
+
+// copies `src` into `tmp` and checks the copy against what was read.
+fn copy_verified(src: &Path, tmp: &Path, metadata: &fs::Metadata) -> std::io::Result<()> {
+    let mut reader = fs::File::open(src)?;
+    let mut writer = fs::File::create_new(tmp)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let len = reader.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.write(&buf[..len]);
+        writer.write_all(&buf[..len])?;
+    }
+    writer.sync_all()?;
+
+    if fs::metadata(tmp)?.len() != metadata.len() || file_hash(tmp)? != hasher.finish() {
+        return Err(std::io::Error::other(format!(
+            "copy of {} is corrupted",
+            src.display()
+        )));
+    }
+
+    fs::set_permissions(tmp, metadata.permissions())?;
+    writer.set_times(
+        FileTimes::new()
+            .set_accessed(metadata.accessed()?)
+            .set_modified(metadata.modified()?),
+    )
+}
+
+/// Copies the file to another filesystem, verifies the copy and removes the source.
+/// The copy is written to a temporary file next to `dst` and renamed into place,
+/// so a failed copy leaves `dst` as it was. Permissions and modification time are preserved.
+/// A symlink is recreated at `dst` with the same target, like a rename would leave it.
+pub fn move_across_devices(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let metadata = fs::symlink_metadata(src)?;
+    let tmp = dst.with_file_name(format!(
+        ".{}.{}.tmp",
+        dst.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id()
+    ));
+
+    if metadata.is_symlink() {
+        let result = fs::read_link(src)
+            .and_then(|target| symlink_file(&target, &tmp))
+            .and_then(|_| fs::rename(&tmp, dst));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        return fs::remove_file(src);
+    }
+
+    let mut spinner = (metadata.len() > LARGE_FILE_LEN).then(|| {
+        Spinner::new(
+            Spinners::CircleHalves,
+            format!(
+                "Copying {} ({} MB) to another filesystem",
+                src.display(),
+                metadata.len() / 1024 / 1024
+            ),
+        )
+    });
+
+    let result = copy_verified(src, &tmp, &metadata).and_then(|_| fs::rename(&tmp, dst));
+
+    if let Some(spinner) = spinner.as_mut() {
+        spinner.stop_with_newline();
+    }
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    fs::remove_file(src)
+}
+
+/// Renames the file, falls back to copy and delete when the destination is on another filesystem.
+pub fn move_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    match fs::rename(src, dst) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => move_across_devices(src, dst),
+        result => result,
+    }
+}
+
+// moves `dst` into the directory of replaced files, keeping its path under `basepath`.
+fn set_aside(basepath: &Path, dst: &Path) -> std::io::Result<PathBuf> {
+    let relative = dst.strip_prefix(basepath).unwrap_or(dst);
+    let backup = basepath.join(REPLACED_DIRNAME).join(relative);
+    // a file kept earlier in this run is never replaced.
+    let backup = match path_exists(&backup) {
+        true => numbered_path(&backup, path_exists),
+        false => backup,
+    };
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_file(dst, &backup)?;
+    Ok(backup)
+}
+
+fn transfer_file(src: &Path, dst: &Path, mode: TransferMode) -> std::io::Result<()> {
+    // rename replaces the destination by itself, links cannot be created over a file and
+    // copying onto a link to the source would truncate the source.
+    if mode != TransferMode::Move && path_exists(dst) {
+        fs::remove_file(dst)?;
+    }
+
+    match mode {
+        TransferMode::Move => move_file(src, dst),
+        TransferMode::Copy => fs::copy(src, dst).map(|_| ()),
+        TransferMode::Symlink => symlink_file(&fs::canonicalize(src)?, dst),
+        TransferMode::Hardlink => fs::hard_link(src, dst),
+    }
+}
+
+pub fn execute_plan(basepath: &PathBuf, plan: &Plan, options: &Options) -> Result<Report, Error> {
+    let Options {
+        verbose,
+        mode,
+        keep_going,
+        ..
+    } = *options;
+    let mut report = Report::default();
+
+    for (filename, reason) in plan.skipped.iter() {
+        skipped(filename.to_string(), reason);
+    }
+
+    if plan.dirs.is_empty() && plan.transfers.is_empty() {
+        return Ok(report);
+    }
+    let mut journal = Journal::create(basepath)?;
+    // where each source file ended up, links point there.
+    let mut placed: HashMap<&Path, PathBuf> = HashMap::new();
+
+    for dirname in plan.dirs.iter() {
+        let dir = basepath.join(dirname);
+        if !dir.exists() {
+            mkdir_for_keyword(dirname.to_string(), basepath)?;
+            journal.record_mkdir(&dir)?;
+        }
+    }
+
+    for Transfer {
+        filename,
+        src,
+        dst,
+        conflict,
+    } in plan.transfers.iter()
+    {
+        let dst = if *conflict == Some(Conflict::Prompt) {
+            match resolve_by_prompt(filename, dst, plan)? {
+                Some(dst) => dst,
+                None => {
                     already_exists(filename.to_string());
                     continue;
                 }
-                let result = fs::rename(src, &dst);
-                if result.is_ok() {
-                    let dst_string = dst.to_str().unwrap().to_string();
-                    if verbose {
-                        moved(filename.to_string(), dst_string.clone());
-                    }
-                    moved_files.push(dst_string);
-                } else {
-                    error(format!("src {}\ndst {}\n", src.display(), dst.display()));
-                    return Err(Error::MoveFileError(result.err().unwrap().to_string()));
+            }
+        } else {
+            dst.to_path_buf()
+        };
+
+        // the source is kept like a replaced file, so that undo can put it back as it was.
+        if *conflict == Some(Conflict::Duplicate) {
+            let result = set_aside(basepath, src);
+            if let Ok(backup) = result.as_ref() {
+                journal.record_duplicate(src, backup)?;
+                placed.insert(src, dst.clone());
+                if verbose {
+                    removed_duplicate(filename.to_string(), dst.display().to_string());
                 }
+                continue;
+            }
+            let err = result.err().unwrap();
+            if !keep_going {
+                error(format!("src {}\n", src.display()));
+                return Err(Error::IOError(err.to_string()));
             }
+            report.failures.push(Failure {
+                src: src.to_path_buf(),
+                dst,
+                error: err.to_string(),
+            });
+            continue;
+        }
+
+        // the replaced file is kept so that undo can put it back.
+        let backup = match path_exists(&dst) {
+            true => set_aside(basepath, &dst).map(Some),
+            false => Ok(None),
+        };
+        let result = backup.and_then(|backup| match transfer_file(src, &dst, mode) {
+            Ok(()) => Ok(backup),
+            Err(err) => {
+                if let Some(backup) = backup {
+                    let _ = move_file(&backup, &dst);
+                }
+                Err(err)
+            }
+        });
+        if let Ok(backup) = result.as_ref() {
+            if let Some(backup) = backup {
+                journal.record_replaced(&dst, backup)?;
+            }
+            journal.record_transfer(mode, src, &dst)?;
+            let dst_string = dst.to_string_lossy().to_string();
+            if verbose {
+                moved(mode, filename.to_string(), dst_string.clone());
+            }
+            report.moved_files.push(dst_string);
+            placed.insert(src, dst);
+        } else if keep_going {
+            report.failures.push(Failure {
+                src: src.to_path_buf(),
+                dst,
+                error: result.err().unwrap().to_string(),
+            });
+        } else {
+            error(format!("src {}\ndst {}\n", src.display(), dst.display()));
+            return Err(Error::MoveFileError(result.err().unwrap().to_string()));
         }
     }
 
-    Ok(moved_files)
+    for link in plan.links.iter() {
+        // the file was skipped at a prompt or failed.
+        let Some(target) = placed.get(link.src.as_path()) else {
+            continue;
+        };
+        let result = transfer_file(target, &link.dst, link.mode);
+        if result.is_ok() {
+            journal.record_transfer(link.mode, target, &link.dst)?;
+            let dst_string = link.dst.to_string_lossy().to_string();
+            if verbose {
+                moved(link.mode, link.filename.to_string(), dst_string.clone());
+            }
+            report.linked_files.push(dst_string);
+        } else if keep_going {
+            report.failures.push(Failure {
+                src: target.to_path_buf(),
+                dst: link.dst.to_path_buf(),
+                error: result.err().unwrap().to_string(),
+            });
+        } else {
+            error(format!(
+                "src {}\ndst {}\n",
+                target.display(),
+                link.dst.display()
+            ));
+            return Err(Error::MoveFileError(result.err().unwrap().to_string()));
+        }
+    }
+
+    for dir in plan.pruned_dirs.iter() {
+        // files skipped at a prompt or failed are still there.
+        let is_empty = fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none());
+        if is_empty {
+            fs::remove_dir(dir)?;
+            journal.record_rmdir(dir)?;
+            if verbose {
+                removed_dir(dir.display().to_string());
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Prints the plan in dry run, otherwise executes it and prints the result.
+pub fn run_plan(basepath: &PathBuf, plan: &Plan, options: &Options) -> Result<Vec<String>, Error> {
+    if options.dry_run {
+        print_plan(plan, options);
+        return Ok(vec![]);
+    }
+
+    let report = execute_plan(basepath, plan, options)?;
+    print_result(plan, options, &report);
+
+    if report.failures.is_empty() {
+        return Ok(report.moved_files);
+    }
+    print_failures(&report.failures);
+
+    if report.moved_files.is_empty() {
+        return Err(Error::MoveFileError(format!(
+            "none of {} files could be moved",
+            report.failures.len()
+        )));
+    }
+    Err(Error::PartialFailure(report.failures.len()))
+}
+
+pub fn move_files_to_dir<P: AsRef<Path>>(
+    basepath: &PathBuf,
+    filenames: &[P],
+    levels: &[Vec<Rule>],
+    options: &Options,
+) -> Result<Vec<String>, Error> {
+    let filenames = filter_files(filenames, options);
+    let plan = plan_moves(basepath, &filenames, levels, options)?;
+
+    run_plan(basepath, &plan, options)
 }
 
+/// Groups files by keyword levels, the first level names the top directories.
 pub fn move_files_to_dir_by_keywords(
-    keywords: Vec<String>,
+    levels: Vec<Vec<Rule>>,
     pathbuf: PathBuf,
-    verbose: bool,
+    options: &Options,
 ) -> Result<(), Error> {
-    let files = files_in_dir(&pathbuf)?;
+    let keyword_dirs = levels
+        .first()
+        .map(|rules| rules.iter().filter_map(Rule::top_dir).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let files = files_in_dir(&pathbuf, options, &keyword_dirs)?;
 
-    let result = move_files_to_dir(&pathbuf, &files, &keywords, verbose);
-    print_result(keywords.len(), result);
+    move_files_to_dir(&pathbuf, &files, &levels, options)?;
 
     Ok(())
 }
@@ -141,6 +950,10 @@ pub fn dirs_in_dir(path: &PathBuf) -> Result<Vec<String>, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::libs::filters::Filters;
+    use crate::libs::normalize::Normalizer;
+    use crate::libs::options::LinkKind;
+    use crate::libs::rule::keywords_to_rules;
     use std::collections::HashSet;
 
     #[test]
@@ -160,10 +973,10 @@ mod tests {
     #[test]
     fn test_mkdir_for_keywords() {
         let tmpdir = std::env::temp_dir();
-        for keyword in vec!["foo", "bar", "(baz)", "[aaa]", "{bbb}"] {
+        for keyword in ["foo", "bar", "(baz)", "[aaa]", "{bbb}"] {
             let dirname = mkdir_for_keyword(keyword.to_string(), &tmpdir).unwrap();
             let new_dir = tmpdir.join(dirname);
-            assert_eq!(new_dir.exists(), true);
+            assert!(new_dir.exists());
         }
     }
 
@@ -191,10 +1004,10 @@ mod tests {
             fs::File::create(&path).unwrap();
         }
 
-        let result = files_in_dir(&tmpdir).unwrap();
+        let result = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
         assert_eq!(result.len(), FILES.len());
         for file in FILES {
-            assert_eq!(result.contains(&file.to_string()), true);
+            assert!(result.contains(&PathBuf::from(file)));
         }
     }
 
@@ -213,15 +1026,270 @@ mod tests {
         }
 
         let keywords = vec![String::from("inquiry"), String::from("invoice")];
-        let files = files_in_dir(&tmpdir).unwrap();
-        let moved_files = move_files_to_dir(&tmpdir, &files, &keywords, true).unwrap();
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let options = Options {
+            verbose: true,
+            ..Default::default()
+        };
+        let moved_files = move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &options,
+        )
+        .unwrap();
         assert_eq!(moved_files.len(), 6);
 
         for file in moved_files.iter() {
-            assert_eq!(Path::new(file).exists(), true);
+            assert!(Path::new(file).exists());
         }
     }
 
+    #[test]
+    fn test_move_files_to_dir_with_filters() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_files_to_dir_with_filters");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        for file in ["invoice_1.pdf", "invoice_2.pdf.part", "invoice_3.txt"] {
+            fs::File::create(tmpdir.join(file)).unwrap();
+        }
+
+        let keywords = vec![String::from("invoice")];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let options = Options {
+            filters: Filters::new(&["*.pdf*".to_string()], &["*.part".to_string()]).unwrap(),
+            ..Default::default()
+        };
+        let moved_files = move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &options,
+        )
+        .unwrap();
+        assert_eq!(moved_files.len(), 1);
+
+        assert!(tmpdir.join("invoice").join("invoice_1.pdf").exists());
+        assert!(tmpdir.join("invoice_2.pdf.part").exists());
+        assert!(tmpdir.join("invoice_3.txt").exists());
+    }
+
+    #[test]
+    fn test_move_files_to_dir_by_regex() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_files_to_dir_by_regex");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        for file in [
+            "invoice_2021-01-01.txt",
+            "invoice_2021-02-01.txt",
+            "invoice_2022-01-01.txt",
+            "inquiry_2021-01-01.txt",
+        ] {
+            fs::File::create(tmpdir.join(file)).unwrap();
+        }
+
+        let levels = vec![vec![
+            Rule::parse(r"re:invoice_(\d{4})=>invoice/{1}").unwrap()
+        ]];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let plan = plan_moves(&tmpdir, &files, &levels, &Options::default()).unwrap();
+        let mut dirs = plan.dirs.clone();
+        dirs[1..].sort();
+        assert_eq!(dirs, vec!["invoice", "invoice/2021", "invoice/2022"]);
+
+        let moved_files = move_files_to_dir(&tmpdir, &files, &levels, &Options::default()).unwrap();
+        assert_eq!(moved_files.len(), 3);
+        assert!(tmpdir.join("invoice/2021/invoice_2021-02-01.txt").exists());
+        assert!(tmpdir.join("inquiry_2021-01-01.txt").exists());
+    }
+
+    #[test]
+    fn test_plan_moves_normalized() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_plan_moves_normalized");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        // decomposed as on macOS.
+        for file in [
+            "\u{30ab}\u{3099}\u{30a4}\u{30c8}\u{3099}_01.pdf",
+            "ｶﾞｲﾄﾞ_02.pdf",
+            "ＡＢＣ_03.pdf",
+        ] {
+            fs::File::create(tmpdir.join(file)).unwrap();
+        }
+
+        let levels = vec![keywords_to_rules(vec![
+            "ガイド".to_string(),
+            "abc".to_string(),
+        ])];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let plan = plan_moves(&tmpdir, &files, &levels, &Options::default()).unwrap();
+        assert_eq!(plan.transfers.len(), 1);
+
+        let options = Options {
+            normalizer: Normalizer {
+                fold_width: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let plan = plan_moves(&tmpdir, &files, &levels, &options).unwrap();
+        assert_eq!(plan.transfers.len(), 3);
+        assert_eq!(plan.dirs.len(), 2);
+    }
+
+    #[test]
+    fn test_plan_moves_priority() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_plan_moves_priority");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        for file in [
+            "tax_invoice_1.pdf",
+            "tax_2.pdf",
+            "tax_3.pdf",
+            "invoice_4.pdf",
+        ] {
+            fs::File::create(tmpdir.join(file)).unwrap();
+        }
+
+        let levels = vec![keywords_to_rules(vec![
+            String::from("tax"),
+            String::from("invoice"),
+        ])];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let chosen = |priority: Priority| {
+            let options = Options {
+                priority,
+                dry_run: true,
+                ..Default::default()
+            };
+            let plan = plan_moves(&tmpdir, &files, &levels, &options).unwrap();
+            assert_eq!(plan.ambiguous.len(), 1);
+            assert_eq!(plan.ambiguous[0].filename, "tax_invoice_1.pdf");
+            assert_eq!(plan.ambiguous[0].dirs, vec!["tax", "invoice"]);
+            plan.ambiguous[0].chosen.clone()
+        };
+
+        assert_eq!(chosen(Priority::Order), Some("tax".to_string()));
+        assert_eq!(chosen(Priority::Longest), Some("invoice".to_string()));
+        // "invoice" matches 2 files and "tax" matches 3.
+        assert_eq!(chosen(Priority::Specific), Some("invoice".to_string()));
+        assert_eq!(chosen(Priority::Ask), None);
+    }
+
+    #[test]
+    fn test_move_files_to_dir_all_matches() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_files_to_dir_all_matches");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        for file in FILES {
+            fs::File::create(tmpdir.join(file)).unwrap();
+        }
+
+        let levels = vec![keywords_to_rules(vec![
+            String::from("inquiry"),
+            String::from("invoice"),
+        ])];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let options = Options {
+            all_matches: Some(LinkKind::Hardlink),
+            ..Default::default()
+        };
+        let plan = plan_moves(&tmpdir, &files, &levels, &options).unwrap();
+        assert_eq!(plan.transfers.len(), 6);
+        assert_eq!(plan.links.len(), 1);
+        assert_eq!(plan.links[0].mode, TransferMode::Hardlink);
+
+        let moved_files = move_files_to_dir(&tmpdir, &files, &levels, &options).unwrap();
+        assert_eq!(moved_files.len(), 6);
+
+        let primary = tmpdir.join("inquiry").join("inquiry_invoice.pdf");
+        let link = tmpdir.join("invoice").join("inquiry_invoice.pdf");
+        assert!(!tmpdir.join("inquiry_invoice.pdf").exists());
+        assert!(primary.exists());
+        assert!(link.exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            assert_eq!(
+                fs::metadata(&primary).unwrap().ino(),
+                fs::metadata(&link).unwrap().ino()
+            );
+        }
+
+        // links are reverted too.
+        crate::libs::journal::undo(&tmpdir, false, false).unwrap();
+        assert!(tmpdir.join("inquiry_invoice.pdf").exists());
+        assert!(!tmpdir.join("invoice").exists());
+    }
+
+    #[test]
+    fn test_move_files_to_nested_dirs() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_files_to_nested_dirs");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        for file in [
+            "acme_invoice_1.pdf",
+            "acme_quote_1.pdf",
+            "acme_notes.txt",
+            "globex_invoice_1.pdf",
+            "invoice_2.pdf",
+        ] {
+            fs::File::create(tmpdir.join(file)).unwrap();
+        }
+
+        let levels = vec![
+            keywords_to_rules(vec![String::from("acme"), String::from("globex")]),
+            keywords_to_rules(vec![String::from("invoice"), String::from("quote")]),
+        ];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let plan = plan_moves(&tmpdir, &files, &levels, &Options::default()).unwrap();
+        let mut dirs = plan.dirs.clone();
+        dirs.sort();
+        assert_eq!(
+            dirs,
+            vec![
+                "acme",
+                "acme/invoice",
+                "acme/quote",
+                "globex",
+                "globex/invoice"
+            ]
+        );
+
+        let moved_files = move_files_to_dir(&tmpdir, &files, &levels, &Options::default()).unwrap();
+        assert_eq!(moved_files.len(), 4);
+        assert!(tmpdir.join("acme/invoice/acme_invoice_1.pdf").exists());
+        assert!(tmpdir.join("acme/quote/acme_quote_1.pdf").exists());
+        assert!(tmpdir.join("acme/acme_notes.txt").exists());
+        assert!(tmpdir.join("globex/invoice/globex_invoice_1.pdf").exists());
+        // the first level does not match.
+        assert!(tmpdir.join("invoice_2.pdf").exists());
+    }
+
     #[test]
     fn test_dirs_in_dir() {
         let tmpdir = std::env::temp_dir();
@@ -231,7 +1299,7 @@ mod tests {
         }
         fs::create_dir(&tmpdir).unwrap();
 
-        let dirnames = vec!["inquiry", "invoice", "questionnaire"];
+        let dirnames = ["inquiry", "invoice", "questionnaire"];
         for dir in dirnames.iter() {
             let path = tmpdir.join(dir);
             println!("path is {:?}", path);
@@ -241,9 +1309,478 @@ mod tests {
         let result = dirs_in_dir(&tmpdir).unwrap();
         assert_eq!(result.len(), dirnames.len());
         let result_set: HashSet<&String> = result.iter().collect();
+        assert!(dirnames.iter().all(|x| result_set.contains(&x.to_string())));
+    }
+
+    #[test]
+    fn test_move_files_to_dir_dry_run() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_files_to_dir_dry_run");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        for file in FILES {
+            let path = tmpdir.join(file);
+            fs::File::create(&path).unwrap();
+        }
+
+        let keywords = vec![String::from("inquiry"), String::from("invoice")];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+
+        let plan = plan_moves(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &on_conflict(OnConflict::Skip),
+        )
+        .unwrap();
+        let mut dirs = plan.dirs.clone();
+        dirs.sort();
+        assert_eq!(dirs, keywords);
+        assert_eq!(plan.transfers.len(), 6);
+        assert_eq!(plan.skipped, vec![]);
+        assert_eq!(
+            plan.ambiguous,
+            vec![Ambiguity {
+                filename: "inquiry_invoice.pdf".to_string(),
+                dirs: keywords.clone(),
+                chosen: Some("inquiry".to_string()),
+            }]
+        );
+
+        let options = Options {
+            dry_run: true,
+            ..Default::default()
+        };
+        let moved_files = move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &options,
+        )
+        .unwrap();
+        assert!(moved_files.is_empty());
+        assert!(!tmpdir.join("inquiry").exists());
+        for file in FILES {
+            assert!(tmpdir.join(file).exists());
+        }
+    }
+
+    fn on_conflict(on_conflict: OnConflict) -> Options {
+        Options {
+            on_conflict,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_numbered_path() {
+        let path = PathBuf::from("dir/file.txt");
+        let taken = [PathBuf::from("dir/file (1).txt")];
+        assert_eq!(
+            numbered_path(&path, |p| taken.contains(&p.to_path_buf())),
+            PathBuf::from("dir/file (2).txt")
+        );
+        assert_eq!(
+            numbered_path(&PathBuf::from("dir/README"), |_| false),
+            PathBuf::from("dir/README (1)")
+        );
+    }
+
+    #[test]
+    fn test_plan_moves_on_conflict() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_plan_moves_on_conflict");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir_all(tmpdir.join("inquiry")).unwrap();
+        fs::write(tmpdir.join("inquiry_1.txt"), "same").unwrap();
+        fs::write(tmpdir.join("inquiry").join("inquiry_1.txt"), "same").unwrap();
+        fs::write(tmpdir.join("inquiry_2.txt"), "new").unwrap();
+        fs::write(tmpdir.join("inquiry").join("inquiry_2.txt"), "old").unwrap();
+
+        let keywords = vec![String::from("inquiry")];
+        let mut files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        files.sort();
+
+        let plan = plan_moves(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &on_conflict(OnConflict::Skip),
+        )
+        .unwrap();
+        assert!(plan.transfers.is_empty());
+        assert_eq!(plan.conflicts_len(), 2);
+
+        let plan = plan_moves(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &on_conflict(OnConflict::Rename),
+        )
+        .unwrap();
         assert_eq!(
-            dirnames.iter().all(|x| result_set.contains(&x.to_string())),
-            true
+            plan.transfers[0].dst,
+            tmpdir.join("inquiry").join("inquiry_1 (1).txt")
         );
+
+        let plan = plan_moves(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &on_conflict(OnConflict::Identical),
+        )
+        .unwrap();
+        assert_eq!(plan.transfers.len(), 1);
+        assert_eq!(plan.transfers[0].conflict, Some(Conflict::Duplicate));
+        assert_eq!(
+            plan.skipped,
+            vec![("inquiry_2.txt".to_string(), SkipReason::AlreadyExists)]
+        );
+
+        let report = execute_plan(&tmpdir, &plan, &Options::default()).unwrap();
+        assert!(report.moved_files.is_empty());
+        assert!(!tmpdir.join("inquiry_1.txt").exists());
+        assert!(tmpdir.join("inquiry_2.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plan_moves_newer_over_dangling_link() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_plan_moves_newer_over_dangling_link");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir_all(tmpdir.join("inquiry")).unwrap();
+        fs::write(tmpdir.join("inquiry_1.txt"), "new").unwrap();
+        std::os::unix::fs::symlink(
+            tmpdir.join("missing.txt"),
+            tmpdir.join("inquiry").join("inquiry_1.txt"),
+        )
+        .unwrap();
+
+        let keywords = vec![String::from("inquiry")];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let plan = plan_moves(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords)],
+            &on_conflict(OnConflict::Newer),
+        )
+        .unwrap();
+        assert_eq!(plan.transfers.len() + plan.conflicts_len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_move_files_to_dir_with_links() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_files_to_dir_with_links");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+        fs::write(tmpdir.join("inquiry_1.txt"), "inquiry").unwrap();
+        fs::write(tmpdir.join("invoice_1.txt"), "invoice").unwrap();
+
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        for (keyword, mode) in [
+            ("inquiry", TransferMode::Symlink),
+            ("invoice", TransferMode::Hardlink),
+        ] {
+            let options = Options {
+                mode,
+                ..Default::default()
+            };
+            let moved_files = move_files_to_dir(
+                &tmpdir,
+                &files,
+                &[keywords_to_rules(vec![keyword.to_string()])],
+                &options,
+            )
+            .unwrap();
+            assert_eq!(moved_files.len(), 1);
+        }
+
+        let symlink = tmpdir.join("inquiry").join("inquiry_1.txt");
+        assert!(symlink.symlink_metadata().unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(symlink).unwrap(), "inquiry");
+        let hardlink = tmpdir.join("invoice").join("invoice_1.txt");
+        assert_eq!(fs::read_to_string(hardlink).unwrap(), "invoice");
+        assert!(tmpdir.join("inquiry_1.txt").exists());
+        assert!(tmpdir.join("invoice_1.txt").exists());
+    }
+
+    #[test]
+    fn test_move_across_devices() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_across_devices");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir_all(tmpdir.join("inquiry")).unwrap();
+        let src = tmpdir.join("inquiry_1.txt");
+        let dst = tmpdir.join("inquiry").join("inquiry_1.txt");
+        fs::write(&src, "inquiry".repeat(100_000)).unwrap();
+        let modified = fs::metadata(&src).unwrap().modified().unwrap();
+        let hash = file_hash(&src).unwrap();
+
+        move_across_devices(&src, &dst).unwrap();
+
+        assert!(!src.exists());
+        assert_eq!(file_hash(&dst).unwrap(), hash);
+        assert_eq!(fs::metadata(&dst).unwrap().modified().unwrap(), modified);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_move_across_devices_link() {
+        let tmpdir = std::env::temp_dir().join("test_move_across_devices_link");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir_all(tmpdir.join("inquiry")).unwrap();
+        let target = tmpdir.join("inquiry_1.txt");
+        fs::write(&target, "inquiry").unwrap();
+        let src = tmpdir.join("inquiry_link.txt");
+        let dst = tmpdir.join("inquiry").join("inquiry_link.txt");
+        std::os::unix::fs::symlink(&target, &src).unwrap();
+
+        move_across_devices(&src, &dst).unwrap();
+
+        assert!(!path_exists(&src));
+        assert!(fs::symlink_metadata(&dst).unwrap().is_symlink());
+        assert_eq!(fs::read_link(&dst).unwrap(), target);
+    }
+
+    #[test]
+    fn test_move_across_devices_failure() {
+        let tmpdir = std::env::temp_dir().join("test_move_across_devices_failure");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir_all(tmpdir.join("inquiry")).unwrap();
+        let dst = tmpdir.join("inquiry").join("inquiry_1.txt");
+        fs::write(&dst, "old").unwrap();
+
+        // a directory can be opened but not read.
+        let src = tmpdir.join("inquiry_1");
+        fs::create_dir(&src).unwrap();
+
+        assert!(move_across_devices(&src, &dst).is_err());
+        assert!(src.exists());
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "old");
+        assert_eq!(fs::read_dir(tmpdir.join("inquiry")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_execute_plan_keep_going() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_execute_plan_keep_going");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+        for file in FILES {
+            fs::File::create(tmpdir.join(file)).unwrap();
+        }
+
+        let keywords = vec![String::from("inquiry"), String::from("invoice")];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let options = Options {
+            keep_going: true,
+            ..Default::default()
+        };
+        let plan = plan_moves(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &options,
+        )
+        .unwrap();
+        // a file disappears after planning.
+        fs::remove_file(tmpdir.join("invoice_1.txt")).unwrap();
+
+        let report = execute_plan(&tmpdir, &plan, &options).unwrap();
+        assert_eq!(report.moved_files.len(), 5);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].src, tmpdir.join("invoice_1.txt"));
+
+        let result = execute_plan(&tmpdir, &plan, &Options::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_move_files_to_dir_recursive() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_move_files_to_dir_recursive");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir_all(tmpdir.join("inquiry")).unwrap();
+        fs::create_dir_all(tmpdir.join("2021").join("01")).unwrap();
+        fs::create_dir_all(tmpdir.join("2022")).unwrap();
+        fs::File::create(tmpdir.join("inquiry").join("inquiry_0.txt")).unwrap();
+        fs::File::create(tmpdir.join("2021").join("inquiry_1.txt")).unwrap();
+        fs::File::create(tmpdir.join("2021").join("01").join("invoice_1.txt")).unwrap();
+        fs::File::create(tmpdir.join("2022").join("invoice_2.txt")).unwrap();
+        fs::File::create(tmpdir.join("2022").join("questionnaire_1.xls")).unwrap();
+
+        let keywords = vec![String::from("inquiry"), String::from("invoice")];
+        let options = Options {
+            recursive: true,
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let mut files = files_in_dir(&tmpdir, &options, &keywords).unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                Path::new("2021").join("inquiry_1.txt"),
+                Path::new("2022").join("invoice_2.txt"),
+                Path::new("2022").join("questionnaire_1.xls"),
+            ]
+        );
+
+        let options = Options {
+            recursive: true,
+            prune_empty: true,
+            ..Default::default()
+        };
+        let files = files_in_dir(&tmpdir, &options, &keywords).unwrap();
+        assert_eq!(files.len(), 4);
+        let moved_files = move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &options,
+        )
+        .unwrap();
+        assert_eq!(moved_files.len(), 3);
+        assert!(tmpdir.join("inquiry").join("inquiry_1.txt").exists());
+        assert!(tmpdir.join("invoice").join("invoice_1.txt").exists());
+        assert!(!tmpdir.join("2021").exists());
+        assert!(tmpdir.join("2022").join("questionnaire_1.xls").exists());
+    }
+
+    #[test]
+    fn test_files_in_dir_hidden_and_symlinks() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_files_in_dir_hidden_and_symlinks");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir_all(tmpdir.join("sub")).unwrap();
+        fs::File::create(tmpdir.join(".inquiry_0.txt")).unwrap();
+        fs::File::create(tmpdir.join("inquiry_1.txt")).unwrap();
+        fs::File::create(tmpdir.join("sub").join("inquiry_2.txt")).unwrap();
+
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        assert_eq!(files, vec![PathBuf::from("inquiry_1.txt")]);
+
+        let options = Options {
+            include_hidden: true,
+            ..Default::default()
+        };
+        let files = files_in_dir(&tmpdir, &options, &[]).unwrap();
+        assert_eq!(files.len(), 2);
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(tmpdir.join("sub"), tmpdir.join("link")).unwrap();
+            std::os::unix::fs::symlink(tmpdir.join("none"), tmpdir.join("broken")).unwrap();
+
+            let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+            assert_eq!(files.len(), 3);
+
+            for (symlinks, len) in [(Symlinks::Follow, 2), (Symlinks::Skip, 2)] {
+                let options = Options {
+                    recursive: true,
+                    symlinks,
+                    ..Default::default()
+                };
+                let files = files_in_dir(&tmpdir, &options, &[]).unwrap();
+                assert_eq!(files.len(), len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_files_in_dir_skips_journal() {
+        let tmpdir = std::env::temp_dir().join("test_files_in_dir_skips_journal");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+        fs::File::create(tmpdir.join("journal_1.txt")).unwrap();
+
+        let keywords = vec![String::from("journal")];
+        let options = Options {
+            include_hidden: true,
+            ..Default::default()
+        };
+        move_files_to_dir_by_keywords(
+            vec![keywords_to_rules(keywords.clone())],
+            tmpdir.clone(),
+            &options,
+        )
+        .unwrap();
+        assert!(tmpdir.join(JOURNAL_FILENAME).exists());
+
+        fs::File::create(tmpdir.join(".journal_2.txt")).unwrap();
+        let files = files_in_dir(&tmpdir, &options, &keywords).unwrap();
+        assert_eq!(files, vec![PathBuf::from(".journal_2.txt")]);
+
+        move_files_to_dir_by_keywords(vec![keywords_to_rules(keywords)], tmpdir.clone(), &options)
+            .unwrap();
+        assert!(tmpdir.join(JOURNAL_FILENAME).exists());
+        assert!(!tmpdir.join("journal").join(JOURNAL_FILENAME).exists());
+
+        crate::libs::journal::undo(&tmpdir, false, false).unwrap();
+        assert!(tmpdir.join(".journal_2.txt").exists());
+        assert!(tmpdir.join("journal").join("journal_1.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_in_dir_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_files_in_dir_non_utf8_names");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"inquiry_\xff.txt");
+        fs::File::create(tmpdir.join(name)).unwrap();
+
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        assert!(files.is_empty());
+
+        let options = Options {
+            non_utf8_names: true,
+            ..Default::default()
+        };
+        let files = files_in_dir(&tmpdir, &options, &[]).unwrap();
+        let keywords = vec![String::from("inquiry")];
+        let moved_files = move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &options,
+        )
+        .unwrap();
+        assert_eq!(moved_files.len(), 1);
+        assert!(tmpdir.join("inquiry").join(name).exists());
+
+        crate::libs::journal::undo(&tmpdir, false, false).unwrap();
+        assert!(tmpdir.join(name).exists());
     }
 }
@@ -0,0 +1,578 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::libs::errors::Error;
+use crate::libs::fs::move_file;
+use crate::libs::options::TransferMode;
+use crate::libs::stdout::*;
+use clap::ValueEnum;
+
+pub static JOURNAL_FILENAME: &str = ".group_files_into_dirs.journal";
+/// Destination files replaced by a run are kept here until the next run.
+pub static REPLACED_DIRNAME: &str = ".group_files_into_dirs.replaced";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Mkdir(PathBuf),
+    /// an emptied source directory was removed.
+    Rmdir(PathBuf),
+    Transfer {
+        mode: TransferMode,
+        src: PathBuf,
+        dst: PathBuf,
+        len: u64,
+        modified: u128,
+    },
+    /// the file at `path` was replaced and set aside to `backup`.
+    Replaced {
+        path: PathBuf,
+        backup: PathBuf,
+        len: u64,
+        modified: u128,
+    },
+    /// the source was set aside to `backup` because the destination has the same contents.
+    Duplicate {
+        src: PathBuf,
+        backup: PathBuf,
+        len: u64,
+        modified: u128,
+    },
+}
+
+/// Records every change of a grouping run, paths are relative to the target directory.
+pub struct Journal {
+    basepath: PathBuf,
+    file: File,
+}
+
+// a symlink is checked by itself, not by the file it points to.
+fn modified_nanos(path: &Path) -> Result<(u64, u128), Error> {
+    let metadata = fs::symlink_metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    Ok((metadata.len(), modified))
+}
+
+// bytes which are not UTF-8, `%` and control characters are percent-encoded,
+// so any file name can be written on a line.
+fn encode_path(path: &Path) -> String {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .utf8_chunks()
+        .fold(String::new(), |mut acc, chunk| {
+            for c in chunk.valid().chars() {
+                if c == '%' || c.is_control() {
+                    let mut buf = [0; 4];
+                    c.encode_utf8(&mut buf)
+                        .bytes()
+                        .for_each(|b| acc.push_str(&format!("%{:02X}", b)));
+                } else {
+                    acc.push(c);
+                }
+            }
+            chunk
+                .invalid()
+                .iter()
+                .for_each(|b| acc.push_str(&format!("%{:02X}", b)));
+            acc
+        })
+}
+
+fn decode_path(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(b) if bytes[i] == b'%' => {
+                decoded.push(b);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    bytes_to_path(decoded)
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).to_string())
+}
+
+fn relative(basepath: &Path, path: &Path) -> String {
+    encode_path(path.strip_prefix(basepath).unwrap_or(path))
+}
+
+impl Journal {
+    /// Starts a new journal, the journal of the previous run and the files it replaced
+    /// are discarded.
+    pub fn create(basepath: &Path) -> Result<Self, Error> {
+        let replaced_dir = basepath.join(REPLACED_DIRNAME);
+        if replaced_dir.exists() {
+            fs::remove_dir_all(&replaced_dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(basepath.join(JOURNAL_FILENAME))?;
+
+        Ok(Journal {
+            basepath: basepath.to_path_buf(),
+            file,
+        })
+    }
+
+    fn write(&mut self, line: String) -> Result<(), Error> {
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    pub fn record_mkdir(&mut self, dir: &Path) -> Result<(), Error> {
+        let dir = relative(&self.basepath, dir);
+        self.write(format!("mkdir\t{}", dir))
+    }
+
+    pub fn record_rmdir(&mut self, dir: &Path) -> Result<(), Error> {
+        let dir = relative(&self.basepath, dir);
+        self.write(format!("rmdir\t{}", dir))
+    }
+
+    fn record_file(&mut self, kind: &str, src: &Path, dst: &Path) -> Result<(), Error> {
+        let (len, modified) = modified_nanos(dst)?;
+        let src = relative(&self.basepath, src);
+        let dst = relative(&self.basepath, dst);
+        self.write(format!("{}\t{}\t{}\t{}\t{}", kind, src, dst, len, modified))
+    }
+
+    pub fn record_transfer(
+        &mut self,
+        mode: TransferMode,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), Error> {
+        self.record_file(mode.verb(), src, dst)
+    }
+
+    pub fn record_replaced(&mut self, path: &Path, backup: &Path) -> Result<(), Error> {
+        self.record_file("replaced", path, backup)
+    }
+
+    pub fn record_duplicate(&mut self, src: &Path, backup: &Path) -> Result<(), Error> {
+        self.record_file("duplicate", src, backup)
+    }
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let fields = line.split('\t').collect::<Vec<_>>();
+    match fields.as_slice() {
+        ["mkdir", dir] => Some(Entry::Mkdir(decode_path(dir))),
+        ["rmdir", dir] => Some(Entry::Rmdir(decode_path(dir))),
+        ["replaced", path, backup, len, modified] => Some(Entry::Replaced {
+            path: decode_path(path),
+            backup: decode_path(backup),
+            len: len.parse().ok()?,
+            modified: modified.parse().ok()?,
+        }),
+        ["duplicate", src, backup, len, modified] => Some(Entry::Duplicate {
+            src: decode_path(src),
+            backup: decode_path(backup),
+            len: len.parse().ok()?,
+            modified: modified.parse().ok()?,
+        }),
+        [kind, src, dst, len, modified] => Some(Entry::Transfer {
+            mode: TransferMode::from_str(kind, false).ok()?,
+            src: decode_path(src),
+            dst: decode_path(dst),
+            len: len.parse().ok()?,
+            modified: modified.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+pub fn read_journal(basepath: &Path) -> Result<Vec<Entry>, Error> {
+    let journal_path = basepath.join(JOURNAL_FILENAME);
+    if !journal_path.exists() {
+        return Err(Error::UndoError(format!(
+            "no journal found in {}",
+            basepath.display()
+        )));
+    }
+
+    fs::read_to_string(&journal_path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            parse_line(line).ok_or(Error::UndoError(format!(
+                "{}:{} is broken",
+                journal_path.display(),
+                i + 1
+            )))
+        })
+        .collect()
+}
+
+// every moved file must be exactly where and how the run left it.
+fn check_entries(basepath: &Path, entries: &[Entry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let (src, dst, len, modified, kept_source) = match entry {
+                Entry::Transfer {
+                    mode,
+                    src,
+                    dst,
+                    len,
+                    modified,
+                } => (src, dst, len, modified, *mode != TransferMode::Move),
+                Entry::Duplicate {
+                    src,
+                    backup,
+                    len,
+                    modified,
+                } => (src, backup, len, modified, false),
+                // the file is put back after the one which replaced it is moved away.
+                Entry::Replaced {
+                    backup,
+                    len,
+                    modified,
+                    ..
+                } => {
+                    return match modified_nanos(&basepath.join(backup)) {
+                        Ok(current) if current == (*len, *modified) => None,
+                        Ok(_) => Some(format!("{} was changed", backup.display())),
+                        Err(_) => Some(format!("{} was moved or removed", backup.display())),
+                    };
+                }
+                Entry::Mkdir(_) | Entry::Rmdir(_) => return None,
+            };
+            // copies and links are removed, so the original file must still be there.
+            if kept_source && !basepath.join(src).exists() {
+                return Some(format!("{} was moved or removed", src.display()));
+            }
+            if !kept_source && basepath.join(src).exists() {
+                return Some(format!("{} already exists", src.display()));
+            }
+            match modified_nanos(&basepath.join(dst)) {
+                Ok(current) if current == (*len, *modified) => None,
+                Ok(_) => Some(format!("{} was changed", dst.display())),
+                Err(_) => Some(format!("{} was moved or removed", dst.display())),
+            }
+        })
+        .collect()
+}
+
+// in dry run files are still in the directory, so they are treated as already moved back.
+fn is_empty_dir(path: &Path, moved_back: &[PathBuf]) -> bool {
+    fs::read_dir(path)
+        .map(|mut entries| entries.all(|e| e.is_ok_and(|e| moved_back.contains(&e.path()))))
+        .unwrap_or(false)
+}
+
+pub fn undo(basepath: &Path, verbose: bool, dry_run: bool) -> Result<Vec<String>, Error> {
+    let entries = read_journal(basepath)?;
+
+    let problems = check_entries(basepath, &entries);
+    if !problems.is_empty() {
+        problems.into_iter().for_each(error);
+        return Err(Error::UndoError(
+            "files were changed since the last run, nothing is reverted".to_string(),
+        ));
+    }
+
+    if dry_run {
+        dry_run_notice();
+    }
+
+    let mut moved_back = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            Entry::Transfer { dst, .. } if dry_run => Some(basepath.join(dst)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut restored_files = vec![];
+    for entry in entries.iter().rev() {
+        match entry {
+            Entry::Transfer {
+                mode: TransferMode::Move,
+                src,
+                dst,
+                ..
+            } => {
+                let src = basepath.join(src);
+                let dst = basepath.join(dst);
+                if !dry_run {
+                    move_file(&dst, &src)?;
+                }
+                if verbose || dry_run {
+                    restored(dst.display().to_string(), src.display().to_string());
+                }
+                restored_files.push(src.display().to_string());
+            }
+            Entry::Transfer { dst, .. } => {
+                let dst = basepath.join(dst);
+                if !dry_run {
+                    fs::remove_file(&dst)?;
+                }
+                if verbose || dry_run {
+                    removed_file(dst.display().to_string());
+                }
+                restored_files.push(dst.display().to_string());
+            }
+            Entry::Replaced { path, backup, .. } => {
+                let path = basepath.join(path);
+                let backup = basepath.join(backup);
+                if !dry_run {
+                    move_file(&backup, &path)?;
+                }
+                if verbose || dry_run {
+                    restored(backup.display().to_string(), path.display().to_string());
+                }
+                restored_files.push(path.display().to_string());
+            }
+            Entry::Duplicate { src, backup, .. } => {
+                let src = basepath.join(src);
+                let backup = basepath.join(backup);
+                if !dry_run {
+                    move_file(&backup, &src)?;
+                }
+                if verbose || dry_run {
+                    restored(backup.display().to_string(), src.display().to_string());
+                }
+                restored_files.push(src.display().to_string());
+            }
+            Entry::Rmdir(dir) => {
+                let dir = basepath.join(dir);
+                if !dry_run {
+                    fs::create_dir_all(&dir)?;
+                }
+                if verbose || dry_run {
+                    created_dir(dir.display().to_string());
+                }
+            }
+            Entry::Mkdir(dir) => {
+                let dir = basepath.join(dir);
+                // the directory would be emptied by the moves above.
+                if is_empty_dir(&dir, &moved_back) {
+                    if !dry_run {
+                        fs::remove_dir(&dir)?;
+                    }
+                    if verbose || dry_run {
+                        removed_dir(dir.display().to_string());
+                    }
+                    // its parent may be emptied by this removal.
+                    if dry_run {
+                        moved_back.push(dir);
+                    }
+                } else {
+                    not_empty(dir.display().to_string());
+                }
+            }
+        }
+    }
+
+    if !dry_run {
+        let replaced_dir = basepath.join(REPLACED_DIRNAME);
+        if replaced_dir.exists() {
+            fs::remove_dir_all(&replaced_dir)?;
+        }
+        fs::remove_file(basepath.join(JOURNAL_FILENAME))?;
+    }
+
+    Ok(restored_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::fs::{files_in_dir, move_files_to_dir};
+    use crate::libs::options::{OnConflict, Options};
+    use crate::libs::rule::keywords_to_rules;
+
+    fn setup(name: &str) -> PathBuf {
+        let tmpdir = std::env::temp_dir().join(name);
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+        for file in ["inquiry_1.txt", "invoice_1.txt", "questionnaire_1.xls"] {
+            File::create(tmpdir.join(file)).unwrap();
+        }
+        tmpdir
+    }
+
+    #[test]
+    fn test_undo() {
+        let tmpdir = setup("test_journal_undo");
+        let keywords = vec![String::from("inquiry"), String::from("invoice")];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &Options::default(),
+        )
+        .unwrap();
+
+        let entries = read_journal(&tmpdir).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(entries.contains(&Entry::Mkdir(PathBuf::from("inquiry"))));
+
+        let restored = undo(&tmpdir, false, false).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert!(tmpdir.join("inquiry_1.txt").exists());
+        assert!(tmpdir.join("invoice_1.txt").exists());
+        assert!(!tmpdir.join("inquiry").exists());
+        assert!(!tmpdir.join("invoice").exists());
+        assert!(!tmpdir.join(JOURNAL_FILENAME).exists());
+    }
+
+    #[test]
+    fn test_undo_refuses_when_files_were_changed() {
+        let tmpdir = setup("test_journal_undo_refuses");
+        let keywords = vec![String::from("inquiry"), String::from("invoice")];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &Options::default(),
+        )
+        .unwrap();
+
+        fs::write(tmpdir.join("inquiry").join("inquiry_1.txt"), "changed").unwrap();
+
+        let result = undo(&tmpdir, false, false);
+        assert!(result.is_err());
+        assert!(tmpdir.join("invoice").join("invoice_1.txt").exists());
+        assert!(tmpdir.join(JOURNAL_FILENAME).exists());
+    }
+
+    #[test]
+    fn test_undo_overwrite() {
+        let tmpdir = setup("test_journal_undo_overwrite");
+        fs::create_dir(tmpdir.join("inquiry")).unwrap();
+        fs::write(tmpdir.join("inquiry").join("inquiry_1.txt"), "old").unwrap();
+        fs::write(tmpdir.join("inquiry_1.txt"), "new").unwrap();
+
+        let keywords = vec![String::from("inquiry")];
+        let options = Options {
+            on_conflict: OnConflict::Overwrite,
+            ..Default::default()
+        };
+        let files = files_in_dir(&tmpdir, &options, &keywords).unwrap();
+        move_files_to_dir(&tmpdir, &files, &[keywords_to_rules(keywords)], &options).unwrap();
+        let replaced = tmpdir.join("inquiry").join("inquiry_1.txt");
+        assert_eq!(fs::read_to_string(&replaced).unwrap(), "new");
+        assert!(tmpdir.join(REPLACED_DIRNAME).exists());
+
+        let restored = undo(&tmpdir, false, false).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(fs::read_to_string(&replaced).unwrap(), "old");
+        assert_eq!(
+            fs::read_to_string(tmpdir.join("inquiry_1.txt")).unwrap(),
+            "new"
+        );
+        assert!(!tmpdir.join(REPLACED_DIRNAME).exists());
+    }
+
+    #[test]
+    fn test_undo_refuses_when_replaced_file_was_changed() {
+        let tmpdir = setup("test_journal_undo_refuses_replaced");
+        fs::create_dir(tmpdir.join("inquiry")).unwrap();
+        fs::write(tmpdir.join("inquiry").join("inquiry_1.txt"), "old").unwrap();
+
+        let keywords = vec![String::from("inquiry")];
+        let options = Options {
+            on_conflict: OnConflict::Overwrite,
+            ..Default::default()
+        };
+        let files = files_in_dir(&tmpdir, &options, &keywords).unwrap();
+        move_files_to_dir(&tmpdir, &files, &[keywords_to_rules(keywords)], &options).unwrap();
+
+        let backup = tmpdir
+            .join(REPLACED_DIRNAME)
+            .join("inquiry")
+            .join("inquiry_1.txt");
+        fs::remove_file(&backup).unwrap();
+
+        assert!(undo(&tmpdir, false, false).is_err());
+        assert!(tmpdir.join("inquiry").join("inquiry_1.txt").exists());
+    }
+
+    #[test]
+    fn test_undo_identical() {
+        let tmpdir = setup("test_journal_undo_identical");
+        fs::create_dir(tmpdir.join("inquiry")).unwrap();
+        fs::write(tmpdir.join("inquiry").join("inquiry_1.txt"), "same").unwrap();
+        fs::write(tmpdir.join("inquiry_1.txt"), "same").unwrap();
+        let modified = UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        File::options()
+            .write(true)
+            .open(tmpdir.join("inquiry_1.txt"))
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let keywords = vec![String::from("inquiry")];
+        let options = Options {
+            on_conflict: OnConflict::Identical,
+            ..Default::default()
+        };
+        let files = files_in_dir(&tmpdir, &options, &keywords).unwrap();
+        move_files_to_dir(&tmpdir, &files, &[keywords_to_rules(keywords)], &options).unwrap();
+        assert!(!tmpdir.join("inquiry_1.txt").exists());
+
+        undo(&tmpdir, false, false).unwrap();
+        let metadata = fs::metadata(tmpdir.join("inquiry_1.txt")).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        assert!(tmpdir.join("inquiry").join("inquiry_1.txt").exists());
+        assert!(!tmpdir.join(REPLACED_DIRNAME).exists());
+    }
+
+    #[test]
+    fn test_undo_copy() {
+        let tmpdir = setup("test_journal_undo_copy");
+        let keywords = vec![String::from("inquiry")];
+        let files = files_in_dir(&tmpdir, &Options::default(), &[]).unwrap();
+        let options = Options {
+            mode: TransferMode::Copy,
+            ..Default::default()
+        };
+        move_files_to_dir(
+            &tmpdir,
+            &files,
+            &[keywords_to_rules(keywords.clone())],
+            &options,
+        )
+        .unwrap();
+        assert!(tmpdir.join("inquiry_1.txt").exists());
+        assert!(tmpdir.join("inquiry").join("inquiry_1.txt").exists());
+
+        undo(&tmpdir, false, false).unwrap();
+        assert!(tmpdir.join("inquiry_1.txt").exists());
+        assert!(!tmpdir.join("inquiry").exists());
+    }
+}
@@ -1,19 +1,34 @@
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use crate::libs::options::{Options, Strategy};
+use crate::libs::segment::{continues_word, script, segment, Script};
+use crate::libs::stopwords::is_noise;
 
 static EXT_REGEX_STR: &str = r"\.([a-zA-Z0-9]+)$";
 static PAREN_REGEX_STR: &str = r"\((.+?)\)|\[(.+?)\]|\{(.+?)\}";
-static DELIMITERS: [char; 4] = [',', '-', '_', ' '];
+// compiled once, keywords are taken from every file name.
+static EXT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(EXT_REGEX_STR).unwrap());
+static PAREN_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(PAREN_REGEX_STR).unwrap());
+pub static DELIMITERS: [char; 4] = [',', '-', '_', ' '];
+pub static BRACKETS: [char; 6] = ['(', ')', '[', ']', '{', '}'];
+const MAX_PHRASE_WORDS: usize = 3;
+
+fn extract_file_basename(filename: &str) -> String {
+    EXT_REGEX.replace_all(filename, "").to_string()
+}
 
-fn extract_file_basename(filename: &String) -> String {
-    let re = Regex::new(EXT_REGEX_STR).unwrap();
-    re.replace_all(filename, "").to_string()
+/// The extension of the file name in lowercase, without the dot.
+pub fn extract_extension(filename: &str) -> Option<String> {
+    EXT_REGEX
+        .captures(filename)
+        .map(|captures| captures[1].to_lowercase())
 }
 
-pub fn extract_keywords(filename_wo_ext: &String) -> Vec<String> {
+pub fn extract_keywords(filename_wo_ext: &str) -> Vec<String> {
     let filename_wo_ext = extract_file_basename(filename_wo_ext);
-    let re = Regex::new(PAREN_REGEX_STR).unwrap();
-    let mut keywords = re
+    let mut keywords = PAREN_REGEX
         .captures_iter(&filename_wo_ext)
         .filter_map(|captures| {
             let keyword_tuple = captures
@@ -35,7 +50,7 @@ pub fn extract_keywords(filename_wo_ext: &String) -> Vec<String> {
         .collect::<Vec<_>>();
 
     // カッコを削除して、残りの文字列を取得
-    let rest = re.replace_all(&filename_wo_ext, "");
+    let rest = PAREN_REGEX.replace_all(&filename_wo_ext, "");
     let mut rest_vec = rest
         .split(DELIMITERS)
         .filter_map(|s| {
@@ -53,12 +68,37 @@ pub fn extract_keywords(filename_wo_ext: &String) -> Vec<String> {
     keywords
 }
 
+/// Counts keywords of the file names, which are normalized first so that
+/// differently encoded names give the same keywords.
+/// Keywords of `options.strategies` are combined, a keyword found by several
+/// strategies is counted once. Without strategies names are split at delimiters.
 pub fn extract_keywords_and_count_from_filenames(
-    filenames: &Vec<String>,
+    filenames: &[String],
+    options: &Options,
 ) -> HashMap<String, usize> {
+    let strategies = match options.strategies.is_empty() {
+        true => vec![Strategy::Delimiters],
+        false => options.strategies.clone(),
+    };
+
     let keyword_hash: HashMap<String, usize> =
         filenames.iter().fold(HashMap::new(), |mut acc, filename| {
-            let keywords = extract_keywords(filename);
+            let filename = options.normalizer.normalize(filename);
+            let mut keywords: Vec<String> = vec![];
+            for (i, strategy) in strategies.iter().enumerate() {
+                let found = extract_keywords_by_strategy(&filename, *strategy, options)
+                    .into_iter()
+                    .filter(|keyword| keyword.chars().count() > 1);
+                if i == 0 {
+                    keywords.extend(found);
+                    continue;
+                }
+                for keyword in found {
+                    if !keywords.contains(&keyword) {
+                        keywords.push(keyword);
+                    }
+                }
+            }
             keywords.iter().for_each(|keyword| {
                 acc.entry(keyword.clone())
                     .and_modify(|count| *count += 1)
@@ -67,7 +107,88 @@ pub fn extract_keywords_and_count_from_filenames(
             acc
         });
 
+    if strategies.contains(&Strategy::Phrases) {
+        return collapse_phrases(keyword_hash);
+    }
+    keyword_hash
+}
+
+/// Drops the keywords of `options.hide` and the words of the stopword file, ignoring case.
+pub fn remove_noise(
+    keyword_hash: HashMap<String, usize>,
+    options: &Options,
+) -> HashMap<String, usize> {
+    let stopwords = options
+        .stopwords
+        .iter()
+        .map(|word| options.normalizer.normalize(word).to_lowercase())
+        .collect::<HashSet<_>>();
+
+    keyword_hash
+        .into_iter()
+        .filter(|(keyword, _)| {
+            !is_noise(keyword, &options.hide) && !stopwords.contains(&keyword.to_lowercase())
+        })
+        .collect()
+}
+
+/// Phrases of 2 to 3 consecutive words between delimiters, as written in the name,
+/// e.g. `project_alpha` from `project_alpha_spec.txt`. Words in brackets are left out.
+pub fn extract_phrases(filename: &str) -> Vec<String> {
+    let filename_wo_ext = extract_file_basename(filename);
+    let rest = PAREN_REGEX.replace_all(&filename_wo_ext, "");
+
+    // byte ranges of the words.
+    let mut words: Vec<(usize, usize)> = vec![];
+    let mut start = None;
+    for (i, c) in rest.char_indices().chain([(rest.len(), ' ')]) {
+        match (DELIMITERS.contains(&c), start) {
+            (true, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+
+    (2..=MAX_PHRASE_WORDS)
+        .flat_map(|len| words.windows(len).collect::<Vec<_>>())
+        .filter(|window| window.iter().all(|(s, e)| rest[*s..*e].chars().count() > 1))
+        .map(|window| rest[window[0].0..window[window.len() - 1].1].to_string())
+        .collect()
+}
+
+fn phrase_words(keyword: &str) -> Vec<&str> {
+    keyword
+        .split(DELIMITERS)
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+// words joined the same way whatever the delimiters were.
+fn words_key(words: &[&str]) -> String {
+    words.join(" ")
+}
+
+/// Drops keywords which only appear inside a longer phrase,
+/// that is with the same count as the phrase, so the phrase stands for them.
+fn collapse_phrases(keyword_hash: HashMap<String, usize>) -> HashMap<String, usize> {
+    // the shorter runs of words inside each phrase, with the count of the phrase.
+    let mut parts: HashSet<(String, usize)> = HashSet::new();
+    for (phrase, count) in keyword_hash.iter() {
+        let words = phrase_words(phrase);
+        for len in 1..words.len() {
+            for part in words.windows(len) {
+                parts.insert((words_key(part), *count));
+            }
+        }
+    }
+
     keyword_hash
+        .into_iter()
+        .filter(|(keyword, count)| !parts.contains(&(words_key(&phrase_words(keyword)), *count)))
+        .collect()
 }
 
 // sort by count first, then by keyword length
@@ -86,7 +207,7 @@ pub fn sort_by_count_and_keyword_length(
             });
 
     let mut count_vec = histogram.clone().into_keys().collect::<Vec<_>>();
-    count_vec.sort_by(|a, b| b.cmp(&a));
+    count_vec.sort_by(|a, b| b.cmp(a));
 
     let sorted_keyword_vec = count_vec.iter().fold(vec![], |mut acc, count| {
         let keywords = histogram.get_mut(count).unwrap();
@@ -94,9 +215,9 @@ pub fn sort_by_count_and_keyword_length(
             if b.len() == a.len() {
                 let a_lower = a.to_lowercase();
                 let b_lower = b.to_lowercase();
-                return a_lower.chars().nth(0).cmp(&b_lower.chars().nth(0));
+                return a_lower.chars().next().cmp(&b_lower.chars().next());
             }
-            return b.len().cmp(&a.len());
+            b.len().cmp(&a.len())
         });
         keywords.iter().for_each(|keyword| {
             acc.push((keyword.clone(), *count));
@@ -107,28 +228,101 @@ pub fn sort_by_count_and_keyword_length(
     sorted_keyword_vec
 }
 
-#[allow(dead_code)]
-pub fn extract_keywords_from_camel_case(filename_wo_ext: &String) -> Vec<String> {
-    let modified_string = filename_wo_ext
-        .chars()
-        .filter_map(|c: char| match c {
-            'a'..='z' => Some(c.to_string()),
-            'A'..='Z' => Some(format!(" {}", c)),
-            ' ' => None,
-            _ => Some(c.to_string()),
-        })
-        .collect::<String>();
+// where a word can end, by the kind of character.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CharKind {
+    Upper,
+    Lower,
+    Script(Script),
+}
 
-    let keywords = modified_string
-        .split(' ')
-        .map(|x| x.to_string())
-        .collect::<Vec<String>>();
-    return keywords;
+fn char_kind(c: char) -> CharKind {
+    match script(c) {
+        Script::Latin if c.is_uppercase() => CharKind::Upper,
+        Script::Latin => CharKind::Lower,
+        script => CharKind::Script(script),
+    }
+}
+
+/// Splits camelCase and PascalCase words, `invoiceACME2021` into `invoice`, `ACME` and `2021`.
+/// Words also end where the script changes, and a single letter keeps the digits after it, as in `Q3`.
+pub fn extract_keywords_from_camel_case(filename_wo_ext: &str) -> Vec<String> {
+    let chars = filename_wo_ext.chars().collect::<Vec<_>>();
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+
+    // kind of the last character which is not like ー.
+    let mut last: Option<CharKind> = None;
+
+    for (i, c) in chars.iter().enumerate() {
+        if continues_word(*c) && !word.is_empty() {
+            word.push(*c);
+            continue;
+        }
+        let kind = char_kind(*c);
+        if kind == CharKind::Script(Script::Other) {
+            words.push(std::mem::take(&mut word));
+            last = None;
+            continue;
+        }
+        let next = chars.get(i + 1).map(|c| char_kind(*c));
+        let starts_word = match (last, kind) {
+            (None, _) => false,
+            (Some(CharKind::Lower), CharKind::Upper) => true,
+            // the last capital of an acronym starts the next word, `ACMEReport`.
+            (Some(CharKind::Upper), CharKind::Upper) => next == Some(CharKind::Lower),
+            (Some(CharKind::Upper), CharKind::Lower) => false,
+            (Some(prev), kind) => prev != kind,
+        };
+        if starts_word {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(*c);
+        last = Some(kind);
+    }
+    words.push(word);
+
+    let mut keywords: Vec<String> = vec![];
+    for word in words.into_iter().filter(|word| !word.is_empty()) {
+        let is_digits = word.chars().all(|c| script(c) == Script::Digit);
+        match keywords.last_mut() {
+            Some(last)
+                if is_digits
+                    && last.chars().count() == 1
+                    && last.chars().all(|c| script(c) == Script::Latin) =>
+            {
+                last.push_str(&word)
+            }
+            _ => keywords.push(word),
+        }
+    }
+    keywords
+}
+
+fn extract_keywords_by_strategy(
+    filename: &str,
+    strategy: Strategy,
+    options: &Options,
+) -> Vec<String> {
+    let keywords = extract_keywords(filename);
+    match strategy {
+        Strategy::Delimiters => keywords
+            .iter()
+            .flat_map(|keyword| segment(keyword, options.segmentation))
+            .collect(),
+        Strategy::CamelCase => keywords
+            .iter()
+            .flat_map(|keyword| extract_keywords_from_camel_case(keyword))
+            .collect(),
+        Strategy::Phrases => extract_phrases(filename),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::libs::normalize::Normalizer;
+    use crate::libs::options::Segmentation;
 
     #[test]
     fn test_extract_keywords() {
@@ -141,6 +335,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extract_extension() {
+        assert_eq!(extract_extension("invoice.PDF"), Some("pdf".to_string()));
+        assert_eq!(extract_extension("archive.tar.gz"), Some("gz".to_string()));
+        assert_eq!(extract_extension("README"), None);
+        assert_eq!(extract_extension("notes.txt~"), None);
+    }
+
     #[test]
     fn test_extract_from_filenames() {
         let filenames = vec![
@@ -148,7 +350,7 @@ mod tests {
             "(000)[111](222) [444(555)] (9)(00){zzz} aaa_bbb-ccc ddd,fff.txt".to_string(),
             "(000)[111](222) [555(666)] (9)(00){zzz} aaa_bbb-ccc ddd,ggg.txt".to_string(),
         ];
-        let result = extract_keywords_and_count_from_filenames(&filenames);
+        let result = extract_keywords_and_count_from_filenames(&filenames, &Options::default());
         let expected = HashMap::from_iter(vec![
             ("000".to_string(), 3),
             ("111".to_string(), 3),
@@ -169,6 +371,105 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_extract_from_filenames_normalized() {
+        let filenames = vec![
+            "\u{30ab}\u{3099}\u{30a4}\u{30c8}\u{3099}_01.pdf".to_string(),
+            "ガイド_02.pdf".to_string(),
+            "ｶﾞｲﾄﾞ_03.pdf".to_string(),
+        ];
+        let result = extract_keywords_and_count_from_filenames(&filenames, &Options::default());
+        assert_eq!(result.get("ガイド"), Some(&2));
+
+        let options = Options {
+            normalizer: Normalizer {
+                fold_width: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = extract_keywords_and_count_from_filenames(&filenames, &options);
+        assert_eq!(result.get("ガイド"), Some(&3));
+    }
+
+    #[test]
+    fn test_extract_from_filenames_segmented() {
+        let filenames = vec![
+            "2021年度請求書株式会社ABC.pdf".to_string(),
+            "2022年度請求書株式会社ABC.pdf".to_string(),
+        ];
+        let result = extract_keywords_and_count_from_filenames(&filenames, &Options::default());
+        assert_eq!(result.get("請求書"), None);
+
+        let options = Options {
+            segmentation: Segmentation::Dictionary,
+            ..Default::default()
+        };
+        let result = extract_keywords_and_count_from_filenames(&filenames, &options);
+        assert_eq!(result.get("請求書"), Some(&2));
+        assert_eq!(result.get("株式会社ABC"), Some(&2));
+        assert_eq!(result.get("2021"), Some(&1));
+    }
+
+    #[test]
+    fn test_extract_phrases() {
+        assert_eq!(
+            extract_phrases("project_alpha_spec v2.txt"),
+            vec![
+                "project_alpha",
+                "alpha_spec",
+                "spec v2",
+                "project_alpha_spec",
+                "alpha_spec v2"
+            ]
+        );
+        assert_eq!(
+            extract_phrases("(draft) a_project_alpha.txt"),
+            vec!["project_alpha"]
+        );
+        assert!(extract_phrases("notes.txt").is_empty());
+    }
+
+    #[test]
+    fn test_extract_from_filenames_phrases() {
+        let filenames = vec![
+            "project_alpha_spec.txt".to_string(),
+            "project_alpha_notes.txt".to_string(),
+            "project_beta_notes.txt".to_string(),
+        ];
+        let options = Options {
+            strategies: vec![Strategy::Delimiters, Strategy::Phrases],
+            ..Default::default()
+        };
+        let result = extract_keywords_and_count_from_filenames(&filenames, &options);
+
+        assert_eq!(result.get("project_alpha"), Some(&2));
+        assert_eq!(result.get("alpha"), None);
+        assert_eq!(result.get("project"), Some(&3));
+        assert_eq!(result.get("notes"), Some(&2));
+        assert_eq!(result.get("project_alpha_spec"), Some(&1));
+        assert_eq!(result.get("spec"), None);
+        assert_eq!(result.get("alpha_spec"), None);
+    }
+
+    #[test]
+    fn test_extract_from_filenames_phrases_many_files() {
+        // every name is different, so the phrases are counted once.
+        let filenames = (0..5000)
+            .map(|i| format!("scan_{}_page_{}.pdf", i, i * 7))
+            .collect::<Vec<_>>();
+        let options = Options {
+            strategies: vec![Strategy::Delimiters, Strategy::Phrases],
+            ..Default::default()
+        };
+        let result = extract_keywords_and_count_from_filenames(&filenames, &options);
+
+        assert_eq!(result.get("scan"), Some(&5000));
+        assert_eq!(result.get("page"), Some(&5000));
+        assert_eq!(result.get("scan_10_page"), Some(&1));
+        assert_eq!(result.get("10_page"), None);
+    }
+
     #[test]
     fn test_sort_by_count_and_keyword_length() {
         // write a test for sort_by_count_and_keyword_length
@@ -213,5 +514,59 @@ mod tests {
             result,
             vec!["camel", "Case", "File", "Name", "Could", "Be", "Parsed"]
         );
+
+        assert_eq!(
+            extract_keywords_from_camel_case("QuarterlyReportQ3Final"),
+            vec!["Quarterly", "Report", "Q3", "Final"]
+        );
+        assert_eq!(
+            extract_keywords_from_camel_case("invoiceACME2021"),
+            vec!["invoice", "ACME", "2021"]
+        );
+        assert_eq!(
+            extract_keywords_from_camel_case("ACMEReportV2"),
+            vec!["ACME", "Report", "V2"]
+        );
+        assert_eq!(
+            extract_keywords_from_camel_case("請求書ABC2021年度"),
+            vec!["請求書", "ABC", "2021", "年度"]
+        );
+        assert_eq!(
+            extract_keywords_from_camel_case("データDeliveryメモ"),
+            vec!["データ", "Delivery", "メモ"]
+        );
+        assert_eq!(
+            extract_keywords_from_camel_case("佐々木Report"),
+            vec!["佐々木", "Report"]
+        );
+    }
+
+    #[test]
+    fn test_extract_from_filenames_strategies() {
+        let filenames = vec![
+            "QuarterlyReportQ3Final.xlsx".to_string(),
+            "QuarterlyReportQ4Draft.xlsx".to_string(),
+        ];
+        let result = extract_keywords_and_count_from_filenames(&filenames, &Options::default());
+        assert_eq!(result.get("Quarterly"), None);
+
+        let options = Options {
+            strategies: vec![Strategy::CamelCase],
+            ..Default::default()
+        };
+        let result = extract_keywords_and_count_from_filenames(&filenames, &options);
+        assert_eq!(result.get("Quarterly"), Some(&2));
+        assert_eq!(result.get("Report"), Some(&2));
+        assert_eq!(result.get("QuarterlyReportQ3Final"), None);
+
+        let options = Options {
+            strategies: vec![Strategy::Delimiters, Strategy::CamelCase],
+            ..Default::default()
+        };
+        let filenames = vec!["invoice_ACME.pdf".to_string()];
+        let result = extract_keywords_and_count_from_filenames(&filenames, &options);
+        assert_eq!(result.get("invoice"), Some(&1));
+        assert_eq!(result.get("ACME"), Some(&1));
+        assert_eq!(result.len(), 2);
     }
 }
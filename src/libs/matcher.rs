@@ -0,0 +1,112 @@
+use crate::libs::keywords::{BRACKETS, DELIMITERS};
+use crate::libs::normalize::Normalizer;
+use crate::libs::options::MatchMode;
+use crate::libs::segment::is_cjk;
+
+/// How keywords are compared with file names.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Matcher {
+    pub mode: MatchMode,
+    pub normalizer: Normalizer,
+}
+
+// `c` is next to the keyword in the name, `edge` is the keyword's own character on that side.
+// a dot separates the extension. Japanese and Chinese are written without delimiters,
+// so their characters on either side end a word too.
+fn is_boundary(c: Option<char>, edge: char) -> bool {
+    c.is_none_or(|c| {
+        DELIMITERS.contains(&c) || BRACKETS.contains(&c) || c == '.' || is_cjk(c) || is_cjk(edge)
+    })
+}
+
+/// Whether the file name contains the keyword, ignoring case.
+/// With `MatchMode::Word` the keyword has to be a whole token of the name.
+pub fn is_match(keyword: &str, filename: &str, mode: MatchMode) -> bool {
+    let keyword = keyword.to_lowercase();
+    let filename = filename.to_lowercase();
+    if keyword.is_empty() {
+        return false;
+    }
+
+    match mode {
+        MatchMode::Substring => filename.contains(&keyword),
+        MatchMode::Word => filename.match_indices(&keyword).any(|(start, _)| {
+            let before = filename[..start].chars().next_back();
+            let after = filename[start + keyword.len()..].chars().next();
+            is_boundary(before, keyword.chars().next().unwrap())
+                && is_boundary(after, keyword.chars().next_back().unwrap())
+        }),
+    }
+}
+
+impl Matcher {
+    /// `name` as it is compared, regexes are matched against it too.
+    pub fn normalize(&self, name: &str) -> String {
+        self.normalizer.normalize(name)
+    }
+
+    /// `is_match` after normalizing both the keyword and the file name.
+    pub fn is_match(&self, keyword: &str, filename: &str) -> bool {
+        is_match(
+            &self.normalize(keyword),
+            &self.normalize(filename),
+            self.mode,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::options::NormalForm;
+
+    #[test]
+    fn test_is_match_word() {
+        let matched = |filename| is_match("art", filename, MatchMode::Word);
+
+        assert!(matched("art.pdf"));
+        assert!(matched("Art_2021.pdf"));
+        assert!(matched("modern-art, 2021.pdf"));
+        assert!(matched("(art)notes.txt"));
+        assert!(matched("[2021]art{1}.txt"));
+        assert!(matched("party art.pdf"));
+        assert!(!matched("party.pdf"));
+        assert!(!matched("chart.xls"));
+        assert!(!matched("artist.txt"));
+
+        assert!(is_match("333(444)", "[333(444)] aaa.txt", MatchMode::Word));
+
+        assert!(is_match("請求書", "2021年度請求書.pdf", MatchMode::Word));
+        assert!(is_match("年度", "2021年度請求書.pdf", MatchMode::Word));
+        assert!(is_match("abc", "株式会社ABC見積書.pdf", MatchMode::Word));
+        assert!(is_match("2021", "2021年度請求書.pdf", MatchMode::Word));
+        assert!(!is_match("abc", "株式会社ABCD.pdf", MatchMode::Word));
+    }
+
+    #[test]
+    fn test_is_match_substring() {
+        assert!(is_match("art", "party.pdf", MatchMode::Substring));
+        assert!(is_match("ART", "chart.xls", MatchMode::Substring));
+        assert!(!is_match("art", "invoice.pdf", MatchMode::Substring));
+    }
+
+    #[test]
+    fn test_matcher_normalizes() {
+        let nfd = "\u{8acb}\u{6c42}\u{66f8}_\u{30ab}\u{3099}\u{30a4}\u{30c8}\u{3099}.pdf";
+        let matcher = Matcher::default();
+        assert!(matcher.is_match("ガイド", nfd));
+        assert!(!matcher.is_match("ＡＢＣ", "ABC_2021.pdf"));
+
+        let matcher = Matcher {
+            normalizer: Normalizer {
+                form: NormalForm::Nfc,
+                fold_width: true,
+                fold_kana: true,
+            },
+            ..Default::default()
+        };
+        assert!(matcher.is_match("ＡＢＣ", "abc_2021.pdf"));
+        assert!(matcher.is_match("ガイド", "ｶﾞｲﾄﾞ_2021.pdf"));
+        assert!(matcher.is_match("がいど", "ガイド＿2021.pdf"));
+    }
+}
@@ -0,0 +1,94 @@
+use unicode_normalization::UnicodeNormalization;
+
+use crate::libs::options::NormalForm;
+
+/// How names and keywords are normalized before they are compared.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Normalizer {
+    pub form: NormalForm,
+    /// full-width ASCII and half-width katakana are folded to their usual width.
+    pub fold_width: bool,
+    /// hiragana is folded to katakana.
+    pub fold_kana: bool,
+}
+
+// U+3000 is the ideographic space, U+FF00..U+FFEF are the half-width and full-width forms.
+fn is_width_variant(c: char) -> bool {
+    c == '\u{3000}' || ('\u{ff00}'..='\u{ffef}').contains(&c)
+}
+
+// small ぁ to ゖ and the iteration marks ゝ ゞ.
+fn hiragana_to_katakana(c: char) -> char {
+    match c {
+        '\u{3041}'..='\u{3096}' | '\u{309d}'..='\u{309e}' => {
+            char::from_u32(c as u32 + 0x60).unwrap_or(c)
+        }
+        _ => c,
+    }
+}
+
+impl Normalizer {
+    pub fn normalize(&self, s: &str) -> String {
+        let folded = if self.fold_width {
+            s.chars()
+                .flat_map(|c| {
+                    if is_width_variant(c) {
+                        c.to_string().nfkc().collect::<Vec<_>>()
+                    } else {
+                        vec![c]
+                    }
+                })
+                .collect::<String>()
+        } else {
+            s.to_string()
+        };
+
+        // voiced sound marks left by half-width katakana are composed here too.
+        let normalized = match self.form {
+            NormalForm::Nfc => folded.nfc().collect::<String>(),
+            NormalForm::Nfkc => folded.nfkc().collect::<String>(),
+        };
+
+        if self.fold_kana {
+            normalized.chars().map(hiragana_to_katakana).collect()
+        } else {
+            normalized
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let nfc = Normalizer::default();
+        assert_eq!(nfc.normalize("\u{30ab}\u{3099}イト\u{3099}"), "ガイド");
+        assert_eq!(nfc.normalize("ｶﾞｲﾄﾞ"), "ｶﾞｲﾄﾞ");
+        assert_eq!(nfc.normalize("ＡＢＣ"), "ＡＢＣ");
+
+        let width = Normalizer {
+            fold_width: true,
+            ..Default::default()
+        };
+        assert_eq!(width.normalize("ｶﾞｲﾄﾞ"), "ガイド");
+        assert_eq!(width.normalize("ＡＢＣ＿２０２１"), "ABC_2021");
+        assert_eq!(width.normalize("請求書①"), "請求書①");
+
+        let nfkc = Normalizer {
+            form: NormalForm::Nfkc,
+            ..Default::default()
+        };
+        assert_eq!(nfkc.normalize("ｶﾞｲﾄﾞ"), "ガイド");
+        assert_eq!(nfkc.normalize("請求書①"), "請求書1");
+
+        let kana = Normalizer {
+            fold_kana: true,
+            ..Default::default()
+        };
+        assert_eq!(kana.normalize("がいど"), "ガイド");
+        assert_eq!(kana.normalize("いすゞ"), "イスヾ");
+        assert_eq!(kana.normalize("ガイド"), "ガイド");
+    }
+}
@@ -0,0 +1,248 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::libs::filters::Filters;
+use crate::libs::matcher::Matcher;
+use crate::libs::normalize::Normalizer;
+
+/// What to do when the destination file already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnConflict {
+    /// leave the file where it is
+    #[default]
+    Skip,
+    /// replace the destination file
+    Overwrite,
+    /// move with a numeric suffix, e.g. `file (1).txt`
+    Rename,
+    /// keep whichever file has the newer modification time
+    Newer,
+    /// remove the source when both files have the same contents, otherwise skip
+    Identical,
+    /// ask for each file
+    Prompt,
+}
+
+impl fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().unwrap();
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/// How a file is placed into its directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransferMode {
+    #[default]
+    Move,
+    /// leave the original file in place and copy it
+    Copy,
+    /// leave the original file in place and create a symbolic link to it
+    Symlink,
+    /// leave the original file in place and create a hard link to it
+    Hardlink,
+}
+
+impl TransferMode {
+    pub fn verb(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::Symlink => "symlink",
+            TransferMode::Hardlink => "hardlink",
+        }
+    }
+
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "moved",
+            TransferMode::Copy => "copied",
+            TransferMode::Symlink => "symlinked",
+            TransferMode::Hardlink => "hardlinked",
+        }
+    }
+}
+
+/// How a keyword is matched against file names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchMode {
+    /// the keyword is a whole token, separated by delimiters, brackets or a dot
+    #[default]
+    Word,
+    /// the keyword appears anywhere in the name
+    Substring,
+}
+
+/// How a file is placed into the other directories it matches.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkKind {
+    Symlink,
+    Hardlink,
+}
+
+impl LinkKind {
+    pub fn mode(&self) -> TransferMode {
+        match self {
+            LinkKind::Symlink => TransferMode::Symlink,
+            LinkKind::Hardlink => TransferMode::Hardlink,
+        }
+    }
+}
+
+/// Which keyword wins when a file matches several keywords.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Priority {
+    /// the keyword given first
+    #[default]
+    Order,
+    /// the longest keyword
+    Longest,
+    /// the keyword matching the fewest files
+    Specific,
+    /// ask for each file
+    Ask,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().unwrap();
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/// What `--by-extension` names directories after.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ByExtension {
+    /// the extension itself, e.g. `pdf`
+    #[default]
+    Extension,
+    /// the kind of file, e.g. `images` or `documents`
+    Category,
+}
+
+/// Where `--by-date` takes the date of a file from.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DateSource {
+    /// the modification time
+    Mtime,
+    /// the creation time, or the status change time where it is not recorded
+    Ctime,
+    /// the date a photo was taken
+    Exif,
+    /// a date like `2021-01-31` in the file name
+    Filename,
+}
+
+/// The Unicode normalization form names and keywords are compared in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NormalForm {
+    /// composed characters, so names from macOS (NFD) match names from Linux
+    #[default]
+    Nfc,
+    /// compatibility characters too, e.g. `①` and `1`, full-width and half-width forms
+    Nfkc,
+}
+
+/// How keywords are split out of file names without delimiters, like Japanese ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Segmentation {
+    /// only at delimiters and brackets
+    #[default]
+    Delimiters,
+    /// also where the script changes, between kanji, kana, Latin letters and digits
+    Script,
+    /// also into words of a bundled dictionary
+    Dictionary,
+}
+
+/// A way of taking keywords out of file names.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// words between delimiters and brackets, split further with `--segment`
+    Delimiters,
+    /// camelCase and PascalCase words, acronyms and digit runs
+    CamelCase,
+    /// 2 and 3 consecutive words as written, e.g. `project_alpha`,
+    /// words only seen inside a phrase are left out
+    Phrases,
+}
+
+/// Keywords interactive mode leaves out of the list.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Noise {
+    /// common words like `copy`, `final` or `scan`
+    Stopwords,
+    /// numbers like `01` or `2021`
+    Numeric,
+    /// years, months and days like `2021`, `202101`, `Jan` or `2021年度`
+    Dates,
+    /// versions like `v2`, `rev3` or `1.0.3`
+    Versions,
+}
+
+/// How symbolic links found while scanning are treated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Symlinks {
+    /// the link itself is grouped like a file
+    #[default]
+    AsFiles,
+    /// links are treated as what they point to
+    Follow,
+    Skip,
+}
+
+/// Settings shared by all grouping modes.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub verbose: bool,
+    pub dry_run: bool,
+    pub on_conflict: OnConflict,
+    pub mode: TransferMode,
+    pub keep_going: bool,
+    pub recursive: bool,
+    /// levels of directories to scan, `None` for no limit.
+    pub max_depth: Option<usize>,
+    /// remove source subdirectories emptied by the run.
+    pub prune_empty: bool,
+    pub include_hidden: bool,
+    pub symlinks: Symlinks,
+    /// group files whose names are not valid UTF-8 instead of skipping them.
+    pub non_utf8_names: bool,
+    /// `--include` and `--exclude` globs applied to the scanned files.
+    pub filters: Filters,
+    pub match_mode: MatchMode,
+    /// how names and keywords are normalized before matching.
+    pub normalizer: Normalizer,
+    /// how interactive mode splits file names into keywords.
+    pub segmentation: Segmentation,
+    /// strategies interactive mode combines, only delimiters when empty.
+    pub strategies: Vec<Strategy>,
+    /// keywords interactive mode leaves out.
+    pub hide: Vec<Noise>,
+    /// words of the stopword file, left out too.
+    pub stopwords: Vec<String>,
+    pub priority: Priority,
+    /// link files into every other matching directory too.
+    pub all_matches: Option<LinkKind>,
+}
+
+impl Options {
+    pub fn matcher(&self) -> Matcher {
+        Matcher {
+            mode: self.match_mode,
+            normalizer: self.normalizer,
+        }
+    }
+}
@@ -1,30 +1,70 @@
 use crate::libs::errors::Error;
+use crate::libs::rule::Rule;
 
-pub fn parse_args(keywords: String) -> Result<Vec<String>, Error> {
-    let keywords = keywords
-        .split(",")
-        .map(|x| x.to_string())
-        .collect::<Vec<String>>();
-
-    keywords.iter().try_for_each(|x| {
-        if x.len() < 2 {
-            return Err(Error::KeywordLengthError(
-                "keyword length must be more than 2".to_string(),
-            ));
+// commas inside brackets belong to a regex, e.g. `re:\d{2,4}`.
+fn split_keywords(keywords: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut current = String::new();
+    let mut depth = 0usize;
+    let mut escaped = false;
+
+    for c in keywords.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                result.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    result.push(current);
+
+    result
+}
+
+/// Parses comma separated rules. Keywords followed by a single `alias=>DIR` are aliases of it too,
+/// so `inv,invoice=>Invoices` is the same as `inv|invoice=>Invoices`.
+/// A rule listing its aliases with `|` keeps the keywords before it on their own.
+pub fn parse_args(keywords: String) -> Result<Vec<Rule>, Error> {
+    let mut rules = vec![];
+    // keywords which could belong to the next alias rule.
+    let mut pending: Vec<String> = vec![];
+
+    for rule in split_keywords(&keywords).iter().map(|x| Rule::parse(x)) {
+        match rule? {
+            Rule::Keyword(keyword) => pending.push(keyword),
+            Rule::Aliases { keywords, dir } if keywords.len() == 1 => {
+                let keywords = pending
+                    .drain(..)
+                    .map(|keyword| keyword.trim().to_string())
+                    .chain(keywords)
+                    .collect();
+                rules.push(Rule::Aliases { keywords, dir });
+            }
+            rule => {
+                rules.extend(pending.drain(..).map(Rule::Keyword));
+                rules.push(rule);
+            }
         }
-        return Ok(());
-    })?;
+    }
+    rules.extend(pending.into_iter().map(Rule::Keyword));
 
-    if keywords.is_empty() {
+    if rules.is_empty() {
         return Err(Error::NoKeywordsFound);
     }
 
-    Ok(keywords)
+    Ok(rules)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::libs::rule::keywords_to_rules;
 
     #[test]
     fn test_parse_args_ok() -> Result<(), Error> {
@@ -32,11 +72,58 @@ mod tests {
 
         assert_eq!(
             keywords,
-            vec!["aa".to_string(), "bb".to_string(), "cc".to_string()]
+            keywords_to_rules(vec!["aa".to_string(), "bb".to_string(), "cc".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_args_regex() -> Result<(), Error> {
+        let keywords = parse_args(r"aa,re:invoice_(\d{2,4})=>invoice/{1},bb".to_string())?;
+
+        assert_eq!(keywords.len(), 3);
+        assert_eq!(
+            keywords[1],
+            Rule::parse(r"re:invoice_(\d{2,4})=>invoice/{1}")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_args_aliases() -> Result<(), Error> {
+        let keywords = parse_args("inv,invoice,請求書 => Invoices".to_string())?;
+        assert_eq!(keywords, vec![Rule::parse("inv|invoice|請求書=>Invoices")?]);
+
+        let keywords = parse_args("quote,inv|invoice=>Invoices".to_string())?;
+        assert_eq!(
+            keywords,
+            vec![
+                Rule::Keyword("quote".to_string()),
+                Rule::parse("inv|invoice=>Invoices")?,
+            ]
+        );
+
+        let keywords = parse_args("inv|invoice=>Invoices,quote,re:(\\d{4})=>{1},memo".to_string())?;
+        assert_eq!(
+            keywords,
+            vec![
+                Rule::parse("inv|invoice=>Invoices")?,
+                Rule::Keyword("quote".to_string()),
+                Rule::parse("re:(\\d{4})=>{1}")?,
+                Rule::Keyword("memo".to_string()),
+            ]
         );
         Ok(())
     }
 
+    #[test]
+    fn test_parse_args_invalid_regex() {
+        assert!(matches!(
+            parse_args("aa,re:invoice_(".to_string()),
+            Err(Error::InvalidRegexError(_))
+        ));
+    }
+
     #[test]
     fn test_parse_args_keyword_is_too_short() {
         if let Err(e) = parse_args("a,b,c".to_string()) {
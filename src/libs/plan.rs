@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::libs::options::TransferMode;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SkipReason {
+    AlreadyMoved,
+    AlreadyExists,
+    NotNewer,
+}
+
+/// How a transfer resolves an existing destination file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Conflict {
+    Overwrite,
+    Rename,
+    Duplicate,
+    Prompt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transfer {
+    pub filename: String,
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    pub conflict: Option<Conflict>,
+}
+
+/// A link to a transferred file in another directory the file matches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub filename: String,
+    /// source of the transfer the link points to.
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    pub mode: TransferMode,
+}
+
+/// A directory a file matching a keyword could be grouped into.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    /// relative to the target path.
+    pub dir: String,
+    /// length of the matched keyword.
+    pub keyword_len: usize,
+}
+
+/// A file matching several keywords, and the directory chosen for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ambiguity {
+    pub filename: String,
+    pub dirs: Vec<String>,
+    pub chosen: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    pub error: String,
+}
+
+/// What a grouping run actually did.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub moved_files: Vec<String>,
+    pub linked_files: Vec<String>,
+    pub failures: Vec<Failure>,
+}
+
+/// Everything a grouping run is going to do, computed before touching the filesystem.
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    /// directories to create, relative to the target path.
+    pub dirs: Vec<String>,
+    pub transfers: Vec<Transfer>,
+    pub links: Vec<Link>,
+    pub skipped: Vec<(String, SkipReason)>,
+    pub ambiguous: Vec<Ambiguity>,
+    /// source subdirectories emptied by the run, deepest first.
+    pub pruned_dirs: Vec<PathBuf>,
+    /// files `--by-date` found no date for.
+    pub undated: Vec<String>,
+    /// sources of the transfers, to look them up without a scan.
+    srcs: HashSet<PathBuf>,
+    /// destinations of the transfers and links.
+    dsts: HashSet<PathBuf>,
+}
+
+impl Plan {
+    pub fn add_dir(&mut self, dirname: &str) {
+        if !self.dirs.iter().any(|d| d == dirname) {
+            self.dirs.push(dirname.to_string());
+        }
+    }
+
+    pub fn add_transfer(&mut self, filename: &str, src: PathBuf, dst: PathBuf) {
+        self.srcs.insert(src.clone());
+        self.dsts.insert(dst.clone());
+        self.transfers.push(Transfer {
+            filename: filename.to_string(),
+            src,
+            dst,
+            conflict: None,
+        });
+    }
+
+    pub fn add_conflicting_transfer(
+        &mut self,
+        filename: &str,
+        src: PathBuf,
+        dst: PathBuf,
+        conflict: Conflict,
+    ) {
+        self.srcs.insert(src.clone());
+        self.dsts.insert(dst.clone());
+        self.transfers.push(Transfer {
+            filename: filename.to_string(),
+            src,
+            dst,
+            conflict: Some(conflict),
+        });
+    }
+
+    pub fn add_link(&mut self, filename: &str, src: PathBuf, dst: PathBuf, mode: TransferMode) {
+        self.dsts.insert(dst.clone());
+        self.links.push(Link {
+            filename: filename.to_string(),
+            src,
+            dst,
+            mode,
+        });
+    }
+
+    pub fn skip(&mut self, filename: &str, reason: SkipReason) {
+        self.skipped.push((filename.to_string(), reason));
+    }
+
+    pub fn has_src(&self, src: &Path) -> bool {
+        self.srcs.contains(src)
+    }
+
+    pub fn has_dst(&self, dst: &Path) -> bool {
+        self.dsts.contains(dst)
+    }
+
+    /// number of files whose destination already exists.
+    pub fn conflicts_len(&self) -> usize {
+        let resolved = self.transfers.iter().filter(|t| t.conflict.is_some());
+        let skipped = self
+            .skipped
+            .iter()
+            .filter(|(_, reason)| *reason != SkipReason::AlreadyMoved);
+        resolved.count() + skipped.count()
+    }
+
+    /// number of distinct directories files are moved or linked into.
+    pub fn target_dirs_len(&self) -> usize {
+        self.transfers
+            .iter()
+            .map(|t| &t.dst)
+            .chain(self.links.iter().map(|l| &l.dst))
+            .filter_map(|dst| dst.parent())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
@@ -0,0 +1,12 @@
+use promkit::preset::listbox::Listbox;
+
+use crate::libs::errors::Error;
+
+/// Asks to pick one of `items`, returns the picked item.
+pub fn select(title: String, items: &[&str]) -> Result<String, Error> {
+    Listbox::new(items)
+        .title(title)
+        .prompt()
+        .and_then(|mut prompt| prompt.run())
+        .map_err(|e| Error::PromptError(e.to_string()))
+}
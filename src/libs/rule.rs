@@ -0,0 +1,422 @@
+use regex::{Regex, RegexBuilder};
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use crate::libs::errors::Error;
+use crate::libs::matcher::Matcher;
+use crate::libs::plan::Candidate;
+
+const REGEX_PREFIX: &str = "re:";
+const TEMPLATE_SEPARATOR: &str = "=>";
+const ALIAS_SEPARATOR: char = '|';
+
+/// A part of a directory name template, e.g. `invoice/{1}`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Group(String),
+}
+
+/// A keyword given with `-k`.
+#[derive(Clone, Debug)]
+pub enum Rule {
+    /// files whose names contain the keyword are grouped into a directory named after it.
+    Keyword(String),
+    /// files whose names contain any of the keywords are grouped into `dir`.
+    Aliases { keywords: Vec<String>, dir: String },
+    /// files whose names match the regex are grouped into a directory built from the template.
+    Regex {
+        regex: Regex,
+        template: String,
+        segments: Vec<Segment>,
+    },
+}
+
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Rule::Keyword(a), Rule::Keyword(b)) => a == b,
+            (
+                Rule::Aliases {
+                    keywords: a,
+                    dir: da,
+                },
+                Rule::Aliases {
+                    keywords: b,
+                    dir: db,
+                },
+            ) => a == b && da == db,
+            (
+                Rule::Regex {
+                    regex: a,
+                    template: ta,
+                    ..
+                },
+                Rule::Regex {
+                    regex: b,
+                    template: tb,
+                    ..
+                },
+            ) => a.as_str() == b.as_str() && ta == tb,
+            _ => false,
+        }
+    }
+}
+
+fn invalid(rule: &str, message: impl std::fmt::Display) -> Error {
+    Error::InvalidRegexError(format!("{}: {}", rule, message))
+}
+
+fn parse_template(rule: &str, template: &str, regex: &Regex) -> Result<Vec<Segment>, Error> {
+    let mut segments = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(invalid(rule, "unclosed `{` in directory template"));
+        };
+        if start > 0 {
+            segments.push(Segment::Text(rest[..start].to_string()));
+        }
+        let group = &rest[start + 1..start + end];
+        let known = match group.parse::<usize>() {
+            Ok(index) => index < regex.captures_len(),
+            Err(_) => regex.capture_names().flatten().any(|name| name == group),
+        };
+        if !known {
+            return Err(invalid(rule, format!("no capture group `{}`", group)));
+        }
+        segments.push(Segment::Group(group.to_string()));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+
+    if Path::new(template)
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(invalid(rule, "directory template must be a relative path"));
+    }
+
+    Ok(segments)
+}
+
+// only plain directory names are allowed, captured text must not escape the target path.
+pub fn is_relative_dir(dir: &str) -> bool {
+    dir.split('/')
+        .all(|name| !name.is_empty() && name != "." && name != "..")
+        && Path::new(dir)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn check_keyword_len(keyword: &str) -> Result<(), Error> {
+    if keyword.len() < 2 {
+        return Err(Error::KeywordLengthError(
+            "keyword length must be more than 2".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn parse_aliases(rule: &str, aliases: &str, dir: &str) -> Result<Rule, Error> {
+    let keywords = aliases
+        .split(ALIAS_SEPARATOR)
+        .map(|keyword| keyword.trim().to_string())
+        .collect::<Vec<_>>();
+    keywords
+        .iter()
+        .try_for_each(|keyword| check_keyword_len(keyword))?;
+
+    let dir = dir.trim();
+    if !is_relative_dir(dir) {
+        return Err(Error::InvalidAliasError(format!(
+            "{}: directory must be a relative path",
+            rule
+        )));
+    }
+
+    Ok(Rule::Aliases {
+        keywords,
+        dir: dir.to_string(),
+    })
+}
+
+impl Rule {
+    /// Parses `keyword`, `alias|alias=>DIR` or `re:PATTERN`, optionally followed by `=>TEMPLATE`.
+    /// Without a template the directory is named after the whole match.
+    pub fn parse(rule: &str) -> Result<Rule, Error> {
+        let Some(pattern) = rule.strip_prefix(REGEX_PREFIX) else {
+            if let Some((aliases, dir)) = rule.rsplit_once(TEMPLATE_SEPARATOR) {
+                return parse_aliases(rule, aliases, dir);
+            }
+            check_keyword_len(rule)?;
+            return Ok(Rule::Keyword(rule.to_string()));
+        };
+
+        let (pattern, template) = match pattern.rsplit_once(TEMPLATE_SEPARATOR) {
+            Some((pattern, template)) => (pattern, template),
+            None => (pattern, "{0}"),
+        };
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| invalid(rule, e))?;
+        let segments = parse_template(rule, template, &regex)?;
+
+        Ok(Rule::Regex {
+            regex,
+            template: template.to_string(),
+            segments,
+        })
+    }
+
+    /// The directory, relative to the target path, the file named `name` is grouped into.
+    /// Keywords are matched with `matcher`, regexes against the normalized name.
+    pub fn target_dir(&self, name: &str, matcher: &Matcher) -> Option<String> {
+        match self {
+            Rule::Keyword(keyword) => matcher.is_match(keyword, name).then(|| keyword.to_string()),
+            Rule::Aliases { keywords, dir } => keywords
+                .iter()
+                .any(|keyword| matcher.is_match(keyword, name))
+                .then(|| dir.to_string()),
+            Rule::Regex {
+                regex, segments, ..
+            } => {
+                let name = matcher.normalize(name);
+                let captures = regex.captures(&name)?;
+                let mut dir = String::new();
+                for segment in segments {
+                    match segment {
+                        Segment::Text(text) => dir.push_str(text),
+                        Segment::Group(group) => {
+                            let capture = match group.parse::<usize>() {
+                                Ok(index) => captures.get(index),
+                                Err(_) => captures.name(group),
+                            };
+                            dir.push_str(capture?.as_str());
+                        }
+                    }
+                }
+                is_relative_dir(&dir).then_some(dir)
+            }
+        }
+    }
+
+    /// Like `target_dir`, with the length of the keyword or the regex match for priority.
+    pub fn candidate(&self, name: &str, matcher: &Matcher) -> Option<Candidate> {
+        let dir = self.target_dir(name, matcher)?;
+        let keyword_len = match self {
+            Rule::Keyword(keyword) => keyword.chars().count(),
+            Rule::Aliases { keywords, .. } => keywords
+                .iter()
+                .filter(|keyword| matcher.is_match(keyword, name))
+                .map(|keyword| keyword.chars().count())
+                .max()
+                .unwrap_or(0),
+            Rule::Regex { regex, .. } => regex
+                .find(&matcher.normalize(name))
+                .map_or(0, |m| m.as_str().chars().count()),
+        };
+        Some(Candidate { dir, keyword_len })
+    }
+
+    /// The top directory files are grouped into when it is known before matching.
+    pub fn top_dir(&self) -> Option<String> {
+        match self {
+            Rule::Keyword(keyword) => Some(keyword.to_string()),
+            Rule::Aliases { dir, .. } => dir.split('/').next().map(|top| top.to_string()),
+            Rule::Regex { segments, .. } => match segments.first() {
+                Some(Segment::Text(text)) => text
+                    .split_once('/')
+                    .map(|(top, _)| top.to_string())
+                    .or_else(|| (segments.len() == 1).then(|| text.to_string())),
+                _ => None,
+            },
+        }
+    }
+}
+
+pub fn keywords_to_rules(keywords: Vec<String>) -> Vec<Rule> {
+    keywords.into_iter().map(Rule::Keyword).collect()
+}
+
+/// Replaces keywords found in the `[aliases]` of the config file, or named after one of its
+/// directories, with the aliases of the directory.
+pub fn apply_aliases(
+    rules: Vec<Rule>,
+    aliases: &BTreeMap<String, Vec<String>>,
+) -> Result<Vec<Rule>, Error> {
+    if let Some(dir) = aliases.keys().find(|dir| !is_relative_dir(dir)) {
+        return Err(Error::InvalidAliasError(format!(
+            "{}: directory must be a relative path",
+            dir
+        )));
+    }
+
+    let rules = rules
+        .into_iter()
+        .map(|rule| {
+            let Rule::Keyword(keyword) = &rule else {
+                return rule;
+            };
+            let lower_keyword = keyword.to_lowercase();
+            let found = aliases.iter().find(|(dir, keywords)| {
+                dir.to_lowercase() == lower_keyword
+                    || keywords.iter().any(|k| k.to_lowercase() == lower_keyword)
+            });
+            match found {
+                Some((dir, keywords)) => {
+                    let mut keywords = keywords.clone();
+                    if !keywords.iter().any(|k| k.to_lowercase() == lower_keyword) {
+                        keywords.push(keyword.to_string());
+                    }
+                    Rule::Aliases {
+                        keywords,
+                        dir: dir.to_string(),
+                    }
+                }
+                None => rule,
+            }
+        })
+        .collect();
+
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::options::MatchMode;
+
+    #[test]
+    fn test_keyword_rule() {
+        let rule = Rule::parse("Invoice").unwrap();
+        assert_eq!(rule, Rule::Keyword("Invoice".to_string()));
+        assert_eq!(
+            rule.target_dir("invoice_2021.pdf", &Matcher::default()),
+            Some("Invoice".to_string())
+        );
+        assert_eq!(
+            rule.target_dir("inquiry_2021.pdf", &Matcher::default()),
+            None
+        );
+        assert_eq!(rule.target_dir("invoices.pdf", &Matcher::default()), None);
+        assert_eq!(
+            rule.target_dir(
+                "invoices.pdf",
+                &Matcher {
+                    mode: MatchMode::Substring,
+                    ..Default::default()
+                }
+            ),
+            Some("Invoice".to_string())
+        );
+        assert_eq!(rule.top_dir(), Some("Invoice".to_string()));
+    }
+
+    #[test]
+    fn test_aliases_rule() {
+        let rule = Rule::parse("inv|invoice|請求書 => Invoices").unwrap();
+        assert_eq!(
+            rule,
+            Rule::Aliases {
+                keywords: vec![
+                    "inv".to_string(),
+                    "invoice".to_string(),
+                    "請求書".to_string()
+                ],
+                dir: "Invoices".to_string(),
+            }
+        );
+        for name in ["inv_01.pdf", "Invoice 2021.pdf", "請求書_2021.pdf"] {
+            assert_eq!(
+                rule.target_dir(name, &Matcher::default()),
+                Some("Invoices".to_string())
+            );
+        }
+        assert_eq!(rule.target_dir("inventory.pdf", &Matcher::default()), None);
+        assert_eq!(rule.top_dir(), Some("Invoices".to_string()));
+
+        assert!(matches!(
+            Rule::parse("inv|i=>Invoices"),
+            Err(Error::KeywordLengthError(_))
+        ));
+        assert!(matches!(
+            Rule::parse("inv=>../Invoices"),
+            Err(Error::InvalidAliasError(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_aliases() {
+        let aliases = BTreeMap::from([(
+            "Invoices".to_string(),
+            vec!["inv".to_string(), "invoice".to_string()],
+        )]);
+        let rules = apply_aliases(
+            keywords_to_rules(vec!["Invoice".to_string(), "quote".to_string()]),
+            &aliases,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules,
+            vec![
+                Rule::Aliases {
+                    keywords: vec!["inv".to_string(), "invoice".to_string()],
+                    dir: "Invoices".to_string(),
+                },
+                Rule::Keyword("quote".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_regex_rule() {
+        let rule = Rule::parse(r"re:invoice_(\d{4})=>invoice/{1}").unwrap();
+        assert_eq!(
+            rule.target_dir("Invoice_2021-01.pdf", &Matcher::default()),
+            Some("invoice/2021".to_string())
+        );
+        assert_eq!(rule.target_dir("invoice_21.pdf", &Matcher::default()), None);
+        assert_eq!(rule.top_dir(), Some("invoice".to_string()));
+
+        let rule = Rule::parse(r"re:(?P<client>\w+)-(\d{4})=>{client}_{2}").unwrap();
+        assert_eq!(
+            rule.target_dir("acme-2022.txt", &Matcher::default()),
+            Some("acme_2022".to_string())
+        );
+        assert_eq!(rule.top_dir(), None);
+
+        let rule = Rule::parse(r"re:\d{4}").unwrap();
+        assert_eq!(
+            rule.target_dir("report 2023.txt", &Matcher::default()),
+            Some("2023".to_string())
+        );
+
+        // captures must not make a path outside the target path.
+        let rule = Rule::parse(r"re:^(\.*)x=>{1}").unwrap();
+        assert_eq!(rule.target_dir("..x", &Matcher::default()), None);
+        assert_eq!(rule.target_dir("x", &Matcher::default()), None);
+    }
+
+    #[test]
+    fn test_invalid_regex_rule() {
+        for rule in [
+            r"re:invoice_(\d{4}",
+            r"re:invoice_(\d{4})=>invoice/{2}",
+            r"re:invoice_(\d{4})=>invoice/{1",
+            r"re:invoice_(\d{4})=>../{1}",
+            r"re:invoice_(\d{4})=>/tmp/{1}",
+        ] {
+            assert!(
+                matches!(Rule::parse(rule), Err(Error::InvalidRegexError(_))),
+                "{}",
+                rule
+            );
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use crate::libs::options::Segmentation;
+
+static DICTIONARY: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    include_str!("segment/dictionary.txt")
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+});
+
+// a company name is written next to these, as in 株式会社ABC or ABC株式会社.
+const COMPANY_MARKERS: [&str; 5] = ["株式会社", "有限会社", "合同会社", "合資会社", "合名会社"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Script {
+    Kanji,
+    Hiragana,
+    Katakana,
+    Latin,
+    Digit,
+    Other,
+}
+
+pub fn script(c: char) -> Script {
+    match c {
+        '\u{3041}'..='\u{309f}' => Script::Hiragana,
+        // without the middle dot ・, which separates words.
+        '\u{30a0}'..='\u{30fa}'
+        | '\u{30fc}'..='\u{30ff}'
+        | '\u{31f0}'..='\u{31ff}'
+        | '\u{ff66}'..='\u{ff9f}' => Script::Katakana,
+        '\u{3005}'..='\u{3007}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}' => Script::Kanji,
+        c if c.is_numeric() => Script::Digit,
+        c if c.is_alphabetic() => Script::Latin,
+        _ => Script::Other,
+    }
+}
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(
+        script(c),
+        Script::Kanji | Script::Hiragana | Script::Katakana
+    )
+}
+
+// ー and 々 continue the word they follow, whatever its script.
+pub fn continues_word(c: char) -> bool {
+    matches!(c, 'ー' | '々' | 'ｰ' | '\u{ff9e}' | '\u{ff9f}')
+}
+
+/// Splits `token` where the script changes, between kanji, kana, Latin letters and digits.
+/// Punctuation is dropped.
+pub fn split_by_script(token: &str) -> Vec<String> {
+    let mut words: Vec<(Script, String)> = vec![];
+    for c in token.chars() {
+        let current = script(c);
+        match words.last_mut() {
+            Some((_, word)) if continues_word(c) => word.push(c),
+            Some((last, word)) if *last == current => word.push(c),
+            _ => words.push((current, c.to_string())),
+        }
+    }
+    words
+        .into_iter()
+        .filter(|(script, _)| *script != Script::Other)
+        .map(|(_, word)| word)
+        .collect()
+}
+
+// the longest dictionary word at the start of `chars`, in chars.
+fn longest_word(chars: &[char], max_len: usize) -> Option<usize> {
+    (2..=max_len.min(chars.len()))
+        .rev()
+        .find(|len| DICTIONARY.contains(chars[..*len].iter().collect::<String>().as_str()))
+}
+
+/// Splits `token` into dictionary words, the rest is split by script.
+/// Company markers are kept with the name next to them.
+pub fn split_by_dictionary(token: &str) -> Vec<String> {
+    let max_len = DICTIONARY
+        .iter()
+        .map(|word| word.chars().count())
+        .max()
+        .unwrap_or(0);
+    let chars = token.chars().collect::<Vec<_>>();
+
+    // (is a dictionary word, word)
+    let mut pieces: Vec<(bool, String)> = vec![];
+    let mut unknown = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match longest_word(&chars[i..], max_len) {
+            Some(len) => {
+                if !unknown.is_empty() {
+                    pieces.push((false, std::mem::take(&mut unknown)));
+                }
+                pieces.push((true, chars[i..i + len].iter().collect()));
+                i += len;
+            }
+            None => {
+                unknown.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    if !unknown.is_empty() {
+        pieces.push((false, unknown));
+    }
+
+    let pieces = pieces
+        .into_iter()
+        .flat_map(|(known, word)| match known {
+            true => vec![(true, word)],
+            false => split_by_script(&word)
+                .into_iter()
+                .map(|word| (false, word))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut words: Vec<String> = vec![];
+    let mut pending_marker: Option<String> = None;
+    for (index, (_, word)) in pieces.iter().enumerate() {
+        if let Some(marker) = pending_marker.take() {
+            words.push(format!("{}{}", marker, word));
+            continue;
+        }
+        if !COMPANY_MARKERS.contains(&word.as_str()) {
+            words.push(word.to_string());
+            continue;
+        }
+        match (
+            pieces.get(index + 1),
+            index.checked_sub(1).map(|i| &pieces[i]),
+        ) {
+            (Some((false, _)), _) => pending_marker = Some(word.to_string()),
+            (_, Some((false, _))) => {
+                let name = words.pop().unwrap_or_default();
+                words.push(format!("{}{}", name, word));
+            }
+            _ => words.push(word.to_string()),
+        }
+    }
+    words
+}
+
+/// Splits a keyword with Japanese or Chinese characters into words, other keywords are kept.
+pub fn segment(token: &str, segmentation: Segmentation) -> Vec<String> {
+    if segmentation == Segmentation::Delimiters || !token.chars().any(is_cjk) {
+        return vec![token.to_string()];
+    }
+    match segmentation {
+        Segmentation::Script => split_by_script(token),
+        _ => split_by_dictionary(token),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_by_script() {
+        assert_eq!(
+            split_by_script("2021年度請求書株式会社ABC"),
+            vec!["2021", "年度請求書株式会社", "ABC"]
+        );
+        assert_eq!(
+            split_by_script("請求書のコピー・メモ"),
+            vec!["請求書", "の", "コピー", "メモ"]
+        );
+        assert_eq!(split_by_script("佐々木さん"), vec!["佐々木", "さん"]);
+    }
+
+    #[test]
+    fn test_split_by_dictionary() {
+        assert_eq!(
+            split_by_dictionary("2021年度請求書株式会社ABC"),
+            vec!["2021", "年度", "請求書", "株式会社ABC"]
+        );
+        assert_eq!(
+            split_by_dictionary("ABC株式会社見積書"),
+            vec!["ABC株式会社", "見積書"]
+        );
+        assert_eq!(split_by_dictionary("株式会社"), vec!["株式会社"]);
+        assert_eq!(
+            split_by_dictionary("会議資料ドラフト"),
+            vec!["会議", "資料", "ドラフト"]
+        );
+    }
+
+    #[test]
+    fn test_segment() {
+        assert_eq!(
+            segment("2021年度請求書", Segmentation::Delimiters),
+            vec!["2021年度請求書"]
+        );
+        assert_eq!(
+            segment("2021年度請求書", Segmentation::Script),
+            vec!["2021", "年度請求書"]
+        );
+        assert_eq!(
+            segment("report2021", Segmentation::Dictionary),
+            vec!["report2021"]
+        );
+    }
+}
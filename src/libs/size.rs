@@ -0,0 +1,150 @@
+use crate::libs::errors::Error;
+use crate::libs::rule::is_relative_dir;
+
+/// Buckets used by `--by-size` without a value.
+pub const DEFAULT_BUCKETS: &str = "small=<1M,medium=1M-100M,large=>100M";
+
+const BUCKET_SEPARATOR: char = ',';
+const NAME_SEPARATOR: char = '=';
+const UNITS: [(&str, u64); 5] = [
+    ("t", 1 << 40),
+    ("g", 1 << 30),
+    ("m", 1 << 20),
+    ("k", 1 << 10),
+    ("", 1),
+];
+
+/// A range of file sizes grouped into the directory `name`.
+/// The lower bound is inclusive and the upper bound exclusive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizeBucket {
+    pub name: String,
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+fn invalid(bucket: &str, message: impl std::fmt::Display) -> Error {
+    Error::InvalidSizeBucketError(format!("{}: {}", bucket, message))
+}
+
+/// Parses a size like `512`, `1.5M` or `100MB`, units are powers of 1024.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_lowercase();
+    let size = size
+        .strip_suffix("ib")
+        .or_else(|| size.strip_suffix('b'))
+        .unwrap_or(&size);
+
+    UNITS.iter().find_map(|(unit, factor)| {
+        let number = size.strip_suffix(unit)?.trim();
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return None;
+        }
+        let number = number.parse::<f64>().ok()?;
+        Some((number * *factor as f64) as u64)
+    })
+}
+
+fn parse_bucket(bucket: &str) -> Result<SizeBucket, Error> {
+    let (name, range) = match bucket.split_once(NAME_SEPARATOR) {
+        Some((name, range)) => (Some(name.trim()), range.trim()),
+        None => (None, bucket.trim()),
+    };
+    let size = |size: &str| {
+        parse_size(size).ok_or_else(|| invalid(bucket, format!("`{}` is not a size", size)))
+    };
+
+    let (min, max, default_name) = if let Some(max) = range.strip_prefix('<') {
+        (0, Some(size(max)?), format!("under_{}", max.trim()))
+    } else if let Some(min) = range.strip_prefix('>') {
+        (size(min)?, None, format!("over_{}", min.trim()))
+    } else if let Some((min, max)) = range.split_once('-') {
+        (size(min)?, Some(size(max)?), range.replace(' ', ""))
+    } else {
+        return Err(invalid(bucket, "use `<SIZE`, `MIN-MAX` or `>SIZE`"));
+    };
+    if max.is_some_and(|max| max <= min) {
+        return Err(invalid(bucket, "the range is empty"));
+    }
+
+    let name = name.map_or(default_name, |name| name.to_string());
+    if !is_relative_dir(&name) {
+        return Err(invalid(bucket, "directory must be a relative path"));
+    }
+
+    Ok(SizeBucket { name, min, max })
+}
+
+/// Parses comma separated buckets, each `[NAME=]<SIZE`, `[NAME=]MIN-MAX` or `[NAME=]>SIZE`.
+/// Without a name the directory is named after the range, e.g. `under_1M`.
+pub fn parse_buckets(buckets: &str) -> Result<Vec<SizeBucket>, Error> {
+    buckets
+        .split(BUCKET_SEPARATOR)
+        .filter(|bucket| !bucket.trim().is_empty())
+        .map(parse_bucket)
+        .collect()
+}
+
+/// The directory of the first bucket a file of `len` bytes falls into.
+pub fn bucket_dir(buckets: &[SizeBucket], len: u64) -> Option<&str> {
+    buckets
+        .iter()
+        .find(|bucket| bucket.min <= len && bucket.max.is_none_or(|max| len < max))
+        .map(|bucket| bucket.name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("1k"), Some(1024));
+        assert_eq!(parse_size("1.5M"), Some(1536 * 1024));
+        assert_eq!(parse_size("100MB"), Some(100 << 20));
+        assert_eq!(parse_size("2GiB"), Some(2 << 30));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("-1M"), None);
+        assert_eq!(parse_size("1X"), None);
+    }
+
+    #[test]
+    fn test_parse_buckets() {
+        let buckets = parse_buckets("<1M, 1M-100M, installers=>100M").unwrap();
+        assert_eq!(
+            buckets,
+            vec![
+                SizeBucket {
+                    name: "under_1M".to_string(),
+                    min: 0,
+                    max: Some(1 << 20),
+                },
+                SizeBucket {
+                    name: "1M-100M".to_string(),
+                    min: 1 << 20,
+                    max: Some(100 << 20),
+                },
+                SizeBucket {
+                    name: "installers".to_string(),
+                    min: 100 << 20,
+                    max: None,
+                },
+            ]
+        );
+
+        assert_eq!(bucket_dir(&buckets, 0), Some("under_1M"));
+        assert_eq!(bucket_dir(&buckets, 1 << 20), Some("1M-100M"));
+        assert_eq!(bucket_dir(&buckets, 100 << 20), Some("installers"));
+
+        let buckets = parse_buckets("1K-1M").unwrap();
+        assert_eq!(bucket_dir(&buckets, 10), None);
+
+        let error = |buckets| parse_buckets(buckets).unwrap_err().to_string();
+        assert!(error("small=<1Q").contains("`1Q` is not a size"));
+        assert!(error("10M-1M").contains("empty"));
+        assert!(error("1M").contains("use `<SIZE`"));
+        assert!(error("../small=<1M").contains("relative path"));
+        assert!(parse_buckets(DEFAULT_BUCKETS).is_ok());
+    }
+}
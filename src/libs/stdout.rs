@@ -1,6 +1,8 @@
 use owo_colors::OwoColorize;
 
 use crate::libs::errors::Error;
+use crate::libs::options::{Options, Priority, TransferMode};
+use crate::libs::plan::{Conflict, Failure, Plan, Report, SkipReason};
 
 fn info(msg: String) {
     println!("{}", msg.blue());
@@ -26,22 +28,215 @@ pub fn already_exists(filename: String) {
     warning(format!("already exists: {}", filename));
 }
 
-pub fn moved(filename: String, dst: String) {
-    info(format!("moved: {} → {}", filename, dst));
+pub fn skipped_entry(path: String, reason: String) {
+    warning(format!("skipped: {} ({})", path, reason));
 }
 
-pub fn print_result(keywords_len: usize, result: Result<Vec<String>, Error>) {
+pub fn not_newer(filename: String) {
+    warning(format!("not newer than existing: {}", filename));
+}
+
+pub fn skipped(filename: String, reason: &SkipReason) {
+    match reason {
+        SkipReason::AlreadyMoved => already_moved(filename),
+        SkipReason::AlreadyExists => already_exists(filename),
+        SkipReason::NotNewer => not_newer(filename),
+    }
+}
+
+pub fn removed_duplicate(filename: String, dst: String) {
+    info(format!("removed duplicate: {} (same as {})", filename, dst));
+}
+
+pub fn moved(mode: TransferMode, filename: String, dst: String) {
+    info(format!("{}: {} → {}", mode.past_tense(), filename, dst));
+}
+
+pub fn restored(filename: String, src: String) {
+    info(format!("restored: {} → {}", filename, src));
+}
+
+pub fn removed_file(filename: String) {
+    info(format!("removed: {}", filename));
+}
+
+pub fn created_dir(dirname: String) {
+    info(format!("created dir: {}", dirname));
+}
+
+pub fn removed_dir(dirname: String) {
+    info(format!("removed dir: {}", dirname));
+}
+
+pub fn not_empty(dirname: String) {
+    warning(format!("not empty, kept: {}", dirname));
+}
+
+fn print_conflicts(plan: &Plan, options: &Options) {
+    let conflicts_len = plan.conflicts_len();
+    if conflicts_len > 0 {
+        info(format!(
+            "{} files already existed in the destination, on conflict: {}.",
+            conflicts_len, options.on_conflict
+        ));
+    }
+}
+
+// links into the other matching directories.
+fn print_links(plan: &Plan, linked_len: usize) {
+    if let Some(link) = plan.links.first() {
+        success(format!(
+            "{} {} files into other matching directories.",
+            link.mode.past_tense(),
+            linked_len
+        ));
+    }
+}
+
+// files matching several keywords.
+fn print_ambiguous(plan: &Plan, options: &Options) {
+    if plan.ambiguous.is_empty() {
+        return;
+    }
+    info(format!(
+        "{} files matched several keywords, priority: {}.",
+        plan.ambiguous.len(),
+        options.priority
+    ));
+    for ambiguity in plan.ambiguous.iter() {
+        let chosen = match (&ambiguity.chosen, options.priority) {
+            (Some(dir), _) => dir.as_str(),
+            (None, Priority::Ask) if options.dry_run => "(ask)",
+            (None, _) => "(skip)",
+        };
+        info(format!(
+            "  {} ({}) → {}",
+            ambiguity.filename,
+            ambiguity.dirs.join(", "),
+            chosen
+        ));
+    }
+}
+
+// files without a date, left in place.
+fn print_undated(plan: &Plan) {
+    if plan.undated.is_empty() {
+        return;
+    }
+    warning(format!(
+        "{} files have no date, left in place:",
+        plan.undated.len()
+    ));
+    for filename in plan.undated.iter() {
+        warning(format!("  {}", filename));
+    }
+}
+
+pub fn print_result(plan: &Plan, options: &Options, report: &Report) {
+    let moved_files = &report.moved_files;
+    if moved_files.is_empty() {
+        info(format!("no files are {}.", options.mode.past_tense()));
+    } else {
+        let msg = format!(
+            "{} {} files to {} directories.",
+            options.mode.past_tense(),
+            moved_files.len(),
+            plan.target_dirs_len()
+        );
+        success(msg)
+    }
+    print_links(plan, report.linked_files.len());
+    print_ambiguous(plan, options);
+    print_undated(plan);
+    print_conflicts(plan, options);
+}
+
+/// Prints failed files grouped by the error.
+pub fn print_failures(failures: &[Failure]) {
+    let mut errors: Vec<&String> = vec![];
+    for failure in failures.iter() {
+        if !errors.contains(&&failure.error) {
+            errors.push(&failure.error);
+        }
+    }
+
+    error(format!("failed {} files:", failures.len()));
+    for err in errors {
+        error(format!("  {}", err));
+        for failure in failures.iter().filter(|f| &f.error == err) {
+            error(format!(
+                "    {} → {}",
+                failure.src.display(),
+                failure.dst.display()
+            ));
+        }
+    }
+}
+
+pub fn dry_run_notice() {
+    warning("dry run: no files are changed.".to_string());
+}
+
+pub fn print_plan(plan: &Plan, options: &Options) {
+    dry_run_notice();
+
+    for dirname in plan.dirs.iter() {
+        info(format!("create dir: {}", dirname));
+    }
+    for transfer in plan.transfers.iter() {
+        let note = match transfer.conflict {
+            Some(Conflict::Overwrite) => " (overwrite)",
+            Some(Conflict::Rename) => " (renamed)",
+            Some(Conflict::Duplicate) => " (identical, remove source)",
+            Some(Conflict::Prompt) => " (already exists, ask)",
+            None => "",
+        };
+        info(format!(
+            "{}: {} → {}{}",
+            options.mode.verb(),
+            transfer.filename,
+            transfer.dst.display(),
+            note
+        ));
+    }
+    for link in plan.links.iter() {
+        info(format!(
+            "{}: {} → {} (also matches)",
+            link.mode.verb(),
+            link.filename,
+            link.dst.display()
+        ));
+    }
+    for (filename, reason) in plan.skipped.iter() {
+        skipped(filename.to_string(), reason);
+    }
+    for dir in plan.pruned_dirs.iter() {
+        info(format!("remove dir: {}", dir.display()));
+    }
+
+    if plan.transfers.is_empty() {
+        info(format!("no files would be {}.", options.mode.past_tense()));
+    } else {
+        success(format!(
+            "would {} {} files to {} directories.",
+            options.mode.verb(),
+            plan.transfers.len(),
+            plan.target_dirs_len()
+        ));
+    }
+    print_links(plan, plan.links.len());
+    print_ambiguous(plan, options);
+    print_undated(plan);
+    print_conflicts(plan, options);
+}
+
+pub fn print_undo_result(result: Result<Vec<String>, Error>) {
     match result {
         Ok(result) => {
-            if result.len() == 0 {
-                info("no files are moved.".to_string());
+            if result.is_empty() {
+                info("no files are restored.".to_string());
             } else {
-                let msg = format!(
-                    "moved {} files to {} directories.",
-                    result.len(),
-                    keywords_len
-                );
-                success(msg)
+                success(format!("restored {} files.", result.len()))
             }
         }
         Err(err) => {
@@ -0,0 +1,134 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::libs::errors::Error;
+use crate::libs::options::Noise;
+
+static BUILTIN: LazyLock<HashSet<String>> = LazyLock::new(|| parse_stopwords(STOPWORDS));
+const STOPWORDS: &str = include_str!("stopwords/stopwords.txt");
+
+// years, months and days as written in names, e.g. `2021`, `202101`, `FY2021`, `Jan`, `2021年度` or `1月`.
+static DATE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?ix)^(?:
+            (?:fy)?(?:19|20)\d{2}(?:[-_./]?(?:0[1-9]|1[0-2])(?:[-_./]?(?:0[1-9]|[12]\d|3[01]))?)?(?:q[1-4])?
+            | q[1-4]
+            | jan(?:uary)? | feb(?:ruary)? | mar(?:ch)? | apr(?:il)? | may | june? | july?
+            | aug(?:ust)? | sept?(?:ember)? | oct(?:ober)? | nov(?:ember)? | dec(?:ember)?
+            | (?:(?:19|20)\d{2}年度?)?(?:\d{1,2}月)?(?:\d{1,2}日)?
+        )$",
+    )
+    .unwrap()
+});
+// e.g. `v2`, `ver1.2`, `rev3`, `1.0.3` or `第2版`.
+static VERSION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(?:(?:v|ver|version|rev|r)\.?\d+(?:[._]\d+)*[a-z]?|\d+(?:\.\d+)+|第?\d+版)$")
+        .unwrap()
+});
+
+// one word per line, `#` starts a comment line.
+fn parse_stopwords(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_lowercase())
+        .collect()
+}
+
+/// Reads a stopword file, one word per line.
+pub fn load_stopwords(path: &Path) -> Result<Vec<String>, Error> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::IOError(format!("{}: {}", path.display(), e)))?;
+    Ok(parse_stopwords(&content).into_iter().collect())
+}
+
+pub fn is_stopword(keyword: &str) -> bool {
+    BUILTIN.contains(&keyword.to_lowercase())
+}
+
+pub fn is_numeric(keyword: &str) -> bool {
+    !keyword.is_empty() && keyword.chars().all(|c| c.is_numeric())
+}
+
+pub fn is_date_like(keyword: &str) -> bool {
+    !keyword.is_empty() && DATE_REGEX.is_match(keyword)
+}
+
+pub fn is_version_like(keyword: &str) -> bool {
+    VERSION_REGEX.is_match(keyword)
+}
+
+/// Whether `keyword` is one of the kinds of noise in `hide`.
+pub fn is_noise(keyword: &str, hide: &[Noise]) -> bool {
+    hide.iter().any(|noise| match noise {
+        Noise::Stopwords => is_stopword(keyword),
+        Noise::Numeric => is_numeric(keyword),
+        Noise::Dates => is_date_like(keyword),
+        Noise::Versions => is_version_like(keyword),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_noise() {
+        assert!(is_stopword("Final"));
+        assert!(is_stopword("コピー"));
+        assert!(!is_stopword("invoice"));
+
+        assert!(is_numeric("01"));
+        assert!(is_numeric("2021"));
+        assert!(!is_numeric("Q3"));
+
+        [
+            "2021",
+            "202101",
+            "20210131",
+            "2021-01",
+            "FY2021",
+            "2021Q3",
+            "Jan",
+            "september",
+        ]
+        .iter()
+        .chain(["2021年度", "2021年1月", "1月", "31日"].iter())
+        .for_each(|keyword| assert!(is_date_like(keyword), "{}", keyword));
+        ["01", "1999x", "20211301", "invoice", "maybe"]
+            .iter()
+            .for_each(|keyword| assert!(!is_date_like(keyword), "{}", keyword));
+
+        ["v2", "V1.2", "ver3", "rev10", "1.0.3", "第2版", "v2b"]
+            .iter()
+            .for_each(|keyword| assert!(is_version_like(keyword), "{}", keyword));
+        ["v", "2", "vacation", "review"]
+            .iter()
+            .for_each(|keyword| assert!(!is_version_like(keyword), "{}", keyword));
+
+        assert!(is_noise("v2", &[Noise::Numeric, Noise::Versions]));
+        assert!(!is_noise("v2", &[Noise::Numeric]));
+        assert!(!is_noise("final", &[]));
+    }
+
+    #[test]
+    fn test_load_stopwords() {
+        let tmpdir = std::env::temp_dir().join("test_load_stopwords");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        let path = tmpdir.join("stopwords.txt");
+        fs::write(&path, "# mine\nScan\n\n  receipt \n").unwrap();
+        let mut stopwords = load_stopwords(&path).unwrap();
+        stopwords.sort();
+        assert_eq!(stopwords, vec!["receipt", "scan"]);
+
+        assert!(load_stopwords(&tmpdir.join("missing.txt")).is_err());
+    }
+}
@@ -5,7 +5,6 @@ use cursive::{
     traits::*,
     views::*,
 };
-use std::collections::HashSet;
 
 use crate::libs::checkbox;
 use crate::libs::errors::Error;
@@ -36,16 +35,65 @@ fn inherit_terminal_colors(siv: &mut cursive::Cursive) {
 
 #[derive(Debug, Default)]
 struct AppState {
-    selected_keywords: HashSet<String>,
+    /// in the order keywords were checked, which is their priority.
+    selected_keywords: Vec<String>,
+    next_level: bool,
     abort: bool,
 }
 
-pub fn run(keywords: Vec<(String, usize)>) -> Result<Vec<String>, Error> {
+fn level_layer(level: usize, keywords: &[(String, usize)]) -> LinearLayout {
+    let title = if level == 1 {
+        "Select keywords. ".to_string()
+    } else {
+        format!("Select keywords for level {} of directories. ", level)
+    };
+
+    LinearLayout::vertical()
+        .child(
+            TextView::new(title)
+                .h_align(HAlign::Left)
+                .v_align(VAlign::Top),
+        )
+        .child(
+            TextView::new(
+                "<Space> to toggle check, <Enter> to run grouping, <n> to select keywords for subdirectories, <q or ESC> to abort.",
+            )
+            .h_align(HAlign::Left)
+            .v_align(VAlign::Top),
+        )
+        .child(DummyView.fixed_height(1))
+        .child(
+            ListView::new()
+                .with(|list| {
+                    for (keyword, count) in keywords {
+                        let k = keyword.clone();
+                        let checkbox = checkbox::Checkbox::new().on_change(move |s, checked| {
+                            let selected_keywords =
+                                &mut s.user_data::<AppState>().unwrap().selected_keywords;
+                            selected_keywords.retain(|x| *x != k);
+                            if checked {
+                                selected_keywords.push(k.clone());
+                            }
+                        });
+                        list.add_child(
+                            "",
+                            LinearLayout::horizontal()
+                                .child(checkbox)
+                                .child(DummyView.fixed_width(1))
+                                .child(TextView::new(format!("{count:>4}  {keyword}"))),
+                        )
+                    }
+                })
+                .scrollable(),
+        )
+        .child(DummyView.fixed_height(1))
+}
+
+/// Shows one screen per level of nested directories, returns selected keywords per level.
+pub fn run(keywords: Vec<(String, usize)>) -> Result<Vec<Vec<String>>, Error> {
     let mut siv = cursive::default();
     inherit_terminal_colors(&mut siv);
 
-    siv.set_user_data(AppState::default());
-
     siv.add_global_callback('q', |s| {
         s.user_data::<AppState>().unwrap().abort = true;
         s.quit();
@@ -55,66 +103,36 @@ pub fn run(keywords: Vec<(String, usize)>) -> Result<Vec<String>, Error> {
         s.quit();
     });
     siv.add_global_callback(event::Key::Enter, |s| s.quit());
+    siv.add_global_callback('n', |s| {
+        s.user_data::<AppState>().unwrap().next_level = true;
+        s.quit();
+    });
     siv.add_global_callback('j', |s| s.on_event(event::Event::Key(event::Key::Down)));
     siv.add_global_callback('k', |s| s.on_event(event::Event::Key(event::Key::Up)));
 
-    siv.add_fullscreen_layer(
-        LinearLayout::vertical()
-            .child(
-                TextView::new("Select keywords. ")
-                    .h_align(HAlign::Left)
-                    .v_align(VAlign::Top),
-            )
-            .child(
-                TextView::new(
-                    "<Space> to toggle check, <Enter> to run grouping, <q or ESC> to abort.",
-                )
-                .h_align(HAlign::Left)
-                .v_align(VAlign::Top),
-            )
-            .child(DummyView.fixed_height(1))
-            .child(
-                ListView::new()
-                    .with(|list| {
-                        for (keyword, count) in keywords {
-                            let k = keyword.clone();
-                            let checkbox =
-                                checkbox::Checkbox::new().on_change(move |s, checked| {
-                                    if checked {
-                                        s.user_data::<AppState>()
-                                            .unwrap()
-                                            .selected_keywords
-                                            .insert(k.clone());
-                                    } else {
-                                        s.user_data::<AppState>()
-                                            .unwrap()
-                                            .selected_keywords
-                                            .remove(&k);
-                                    }
-                                });
-                            list.add_child(
-                                "",
-                                LinearLayout::horizontal()
-                                    .child(checkbox)
-                                    .child(DummyView.fixed_width(1))
-                                    .child(TextView::new(format!("{count:>4}  {keyword}"))),
-                            )
-                        }
-                    })
-                    .scrollable(),
-            )
-            .child(DummyView.fixed_height(1)),
-    );
-
-    siv.run();
+    let mut keywords = keywords;
+    let mut levels = vec![];
+    loop {
+        siv.set_user_data(AppState::default());
+        siv.add_fullscreen_layer(level_layer(levels.len() + 1, &keywords));
+        siv.run();
+        siv.pop_layer();
 
-    let state = siv.user_data::<AppState>().unwrap();
+        let state = siv.take_user_data::<AppState>().unwrap();
+        if state.abort {
+            return Ok(vec![]);
+        }
+        if state.selected_keywords.is_empty() {
+            break;
+        }
 
-    if state.abort {
-        return Ok(vec![]);
+        // a keyword names directories of one level only.
+        keywords.retain(|(keyword, _)| !state.selected_keywords.contains(keyword));
+        levels.push(state.selected_keywords);
+        if !state.next_level || keywords.is_empty() {
+            break;
+        }
     }
 
-    let selected_keywords = state.selected_keywords.clone().into_iter().collect();
-
-    Ok(selected_keywords)
+    Ok(levels)
 }
@@ -1,14 +1,32 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
 mod libs;
 mod workflow;
 
+use libs::config::{load_config, Config, Settings};
+use libs::date::DateTemplate;
 use libs::errors::Error;
+use libs::file_type::Category;
+use libs::filters::Filters;
 use libs::fs::{move_files_to_dir_by_keywords, parse_path};
+use libs::journal;
+use libs::normalize::Normalizer;
+use libs::options::{
+    ByExtension, DateSource, LinkKind, MatchMode, Noise, NormalForm, OnConflict, Options, Priority,
+    Segmentation, Strategy, Symlinks, TransferMode,
+};
 use libs::parse_args::parse_args;
+use libs::rule::{apply_aliases, keywords_to_rules};
+use libs::size::{parse_buckets, DEFAULT_BUCKETS};
+use libs::stdout::{error, print_undo_result};
+use libs::stopwords::load_stopwords;
 use libs::tui;
-use workflow::{dirs_as_keywords, interactive};
+use workflow::{by_date, by_extension, by_size, dirs_as_keywords, interactive};
 
 #[derive(Parser, Debug)]
 pub struct Args {
@@ -18,48 +36,441 @@ pub struct Args {
         short
     )]
     pub keywords: Option<String>,
-    #[clap(required = true)]
-    pub path: String,
+    #[clap(
+        help = "Keywords for the next level of nested directories, can be repeated",
+        long,
+        value_name = "KEYWORDS"
+    )]
+    pub level: Vec<String>,
+    #[clap(required_unless_present = "print_config")]
+    pub path: Option<String>,
     #[clap(help = "Verbose output", long, short)]
     pub verbose: bool,
     #[clap(help = "Use directory as keyword", long, short)]
     pub dir_as_keyword: bool,
+    #[clap(
+        help = "Print which files would be moved where without changing anything",
+        long,
+        short = 'n'
+    )]
+    pub dry_run: bool,
+    #[clap(
+        help = "Revert the last grouping run in the directory",
+        long,
+        conflicts_with_all = ["keywords", "level", "dir_as_keyword", "by_extension", "by_date", "by_size"]
+    )]
+    pub undo: bool,
+    #[clap(
+        help = "What to do when the destination file already exists",
+        long,
+        value_enum,
+        default_value_t = OnConflict::Skip
+    )]
+    pub on_conflict: OnConflict,
+    #[clap(
+        help = "How files are placed into directories",
+        long,
+        value_enum,
+        default_value_t = TransferMode::Move
+    )]
+    pub mode: TransferMode,
+    #[clap(
+        help = "Keep processing the rest of files when a file cannot be moved",
+        long
+    )]
+    pub keep_going: bool,
+    // with `-d` every subdirectory is a keyword directory, so there is nothing to recurse into.
+    #[clap(
+        help = "Collect files from subdirectories too",
+        long,
+        short,
+        conflicts_with = "dir_as_keyword"
+    )]
+    pub recursive: bool,
+    #[clap(
+        help = "Levels of subdirectories to collect files from, implies --recursive",
+        long,
+        conflicts_with = "dir_as_keyword"
+    )]
+    pub max_depth: Option<usize>,
+    #[clap(help = "Remove subdirectories emptied by the run", long)]
+    pub prune_empty: bool,
+    #[clap(help = "Group files whose names start with a dot too", long)]
+    pub include_hidden: bool,
+    #[clap(help = "Treat symbolic links as what they point to", long)]
+    pub follow_symlinks: bool,
+    #[clap(
+        help = "Ignore symbolic links",
+        long,
+        conflicts_with = "follow_symlinks"
+    )]
+    pub skip_symlinks: bool,
+    #[clap(
+        help = "Group files whose names are not valid UTF-8 instead of skipping them",
+        long
+    )]
+    pub non_utf8_names: bool,
+    #[clap(
+        help = "Only group files matching the glob, can be repeated",
+        long,
+        value_name = "GLOB"
+    )]
+    pub include: Vec<String>,
+    #[clap(
+        help = "Leave files matching the glob alone, can be repeated",
+        long,
+        value_name = "GLOB"
+    )]
+    pub exclude: Vec<String>,
+    #[clap(
+        help = "Read settings from this file instead of the default config file",
+        long,
+        value_name = "PATH"
+    )]
+    pub config: Option<PathBuf>,
+    #[clap(
+        help = "How keywords are matched against file names",
+        long = "match",
+        value_enum,
+        default_value_t = MatchMode::Word
+    )]
+    pub match_mode: MatchMode,
+    #[clap(
+        help = "Unicode normalization applied to names and keywords before matching",
+        long,
+        value_enum,
+        default_value_t = NormalForm::Nfc
+    )]
+    pub normalize: NormalForm,
+    #[clap(
+        help = "Match full-width and half-width forms, e.g. ＡＢＣ and ABC, ｶﾞｲﾄﾞ and ガイド",
+        long
+    )]
+    pub fold_width: bool,
+    #[clap(help = "Match hiragana and katakana, e.g. がいど and ガイド", long)]
+    pub fold_kana: bool,
+    #[clap(
+        help = "How interactive mode splits names without delimiters, like Japanese ones, into keywords",
+        long,
+        value_enum,
+        default_value_t = Segmentation::Delimiters
+    )]
+    pub segment: Segmentation,
+    #[clap(
+        help = "Ways interactive mode takes keywords out of file names, combined when several",
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "delimiters"
+    )]
+    pub strategy: Vec<Strategy>,
+    #[clap(
+        help = "Keywords interactive mode leaves out, comma separated",
+        long,
+        value_enum,
+        value_delimiter = ','
+    )]
+    pub hide: Vec<Noise>,
+    #[clap(
+        help = "Leave the words of this file out of interactive mode, one per line",
+        long,
+        value_name = "PATH"
+    )]
+    pub stopwords_file: Option<PathBuf>,
+    #[clap(
+        help = "Which keyword wins when a file matches several keywords",
+        long,
+        value_enum,
+        default_value_t = Priority::Order
+    )]
+    pub priority: Priority,
+    #[clap(
+        help = "Also link files into every other directory they match, symlink by default",
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "symlink"
+    )]
+    pub all_matches: Option<LinkKind>,
+    #[clap(
+        help = "Group files into directories named after their extensions or categories",
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "extension",
+        conflicts_with_all = ["keywords", "level", "dir_as_keyword"]
+    )]
+    pub by_extension: Option<ByExtension>,
+    #[clap(
+        help = "Tell the type of files without a known extension from their first bytes",
+        long
+    )]
+    pub sniff: bool,
+    #[clap(
+        help = "Group files into directories named after their dates, {yyyy}/{mm} by default",
+        long,
+        value_name = "TEMPLATE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "{yyyy}/{mm}",
+        conflicts_with_all = ["keywords", "level", "dir_as_keyword", "by_extension"]
+    )]
+    pub by_date: Option<String>,
+    #[clap(
+        help = "Where dates are taken from, the first source with a date is used",
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "mtime"
+    )]
+    pub date_source: Vec<DateSource>,
+    #[clap(
+        help = "Move files without a date into this directory instead of leaving them",
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "undated"
+    )]
+    pub undated_dir: Option<String>,
+    #[clap(
+        help = "Group files into size buckets, e.g. small=<1M,medium=1M-100M,large=>100M",
+        long,
+        value_name = "BUCKETS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_BUCKETS,
+        conflicts_with_all = ["keywords", "level", "dir_as_keyword", "by_extension", "by_date"]
+    )]
+    pub by_size: Option<String>,
+    #[clap(help = "Use settings of the named profile in the config file", long)]
+    pub profile: Option<String>,
+    #[clap(
+        help = "Print the settings merged from the config file and flags, then exit",
+        long
+    )]
+    pub print_config: bool,
 }
 
-fn main() -> Result<(), Error> {
-    let Args {
-        keywords,
-        path,
+// 2 is used by clap for usage errors.
+const EXIT_PARTIAL_FAILURE: u8 = 3;
+
+fn main() -> ExitCode {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match run(args, &matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error(format!("{}", err));
+            match err {
+                Error::PartialFailure(_) => ExitCode::from(EXIT_PARTIAL_FAILURE),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+// values given on the command line, others are left to the profile.
+fn command_line_settings(args: &Args, matches: &ArgMatches) -> Settings {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let flag = |id: &str, value: bool| given(id).then_some(value);
+
+    Settings {
+        keywords: args.keywords.clone(),
+        levels: given("level").then(|| args.level.clone()),
+        dir_as_keyword: flag("dir_as_keyword", args.dir_as_keyword),
+        verbose: flag("verbose", args.verbose),
+        on_conflict: given("on_conflict").then_some(args.on_conflict),
+        mode: given("mode").then_some(args.mode),
+        keep_going: flag("keep_going", args.keep_going),
+        recursive: flag("recursive", args.recursive),
+        max_depth: args.max_depth,
+        prune_empty: flag("prune_empty", args.prune_empty),
+        include_hidden: flag("include_hidden", args.include_hidden),
+        follow_symlinks: flag("follow_symlinks", args.follow_symlinks),
+        skip_symlinks: flag("skip_symlinks", args.skip_symlinks),
+        non_utf8_names: flag("non_utf8_names", args.non_utf8_names),
+        include: given("include").then(|| args.include.clone()),
+        exclude: given("exclude").then(|| args.exclude.clone()),
+        match_mode: given("match_mode").then_some(args.match_mode),
+        normalize: given("normalize").then_some(args.normalize),
+        fold_width: flag("fold_width", args.fold_width),
+        fold_kana: flag("fold_kana", args.fold_kana),
+        segment: given("segment").then_some(args.segment),
+        strategy: given("strategy").then(|| args.strategy.clone()),
+        hide: given("hide").then(|| args.hide.clone()),
+        stopwords_file: args.stopwords_file.clone(),
+        priority: given("priority").then_some(args.priority),
+        all_matches: args.all_matches,
+        by_extension: args.by_extension,
+        sniff: flag("sniff", args.sniff),
+        by_date: args.by_date.clone(),
+        date_source: given("date_source").then(|| args.date_source.clone()),
+        undated_dir: args.undated_dir.clone(),
+        by_size: args.by_size.clone(),
+    }
+}
+
+// the settings and aliases in effect, in the config file format.
+fn print_config(settings: &Settings, config: &Config) -> Result<(), Error> {
+    #[derive(Serialize)]
+    struct Effective<'a> {
+        #[serde(flatten)]
+        settings: &'a Settings,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        aliases: &'a BTreeMap<String, Vec<String>>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        categories: &'a BTreeMap<Category, String>,
+    }
+
+    let effective = Effective {
+        settings,
+        aliases: &config.aliases,
+        categories: &config.categories,
+    };
+    let content = toml::to_string(&effective).map_err(|e| Error::ConfigError(e.to_string()))?;
+    print!("{}", content);
+    Ok(())
+}
+
+fn run(args: Args, matches: &ArgMatches) -> Result<(), Error> {
+    let load_settings = || -> Result<(Config, Settings), Error> {
+        let config = load_config(args.config.as_deref())?;
+        let settings = config
+            .settings(args.profile.as_deref())?
+            .merge(command_line_settings(&args, matches))
+            .resolved();
+        Ok((config, settings))
+    };
+
+    if args.print_config {
+        let (config, settings) = load_settings()?;
+        return print_config(&settings, &config);
+    }
+
+    let pathbuf = parse_path(args.path.clone().unwrap_or_default())?;
+
+    // undo does not depend on the config file.
+    if args.undo {
+        return undo_last_run(pathbuf, args.verbose, args.dry_run);
+    }
+
+    let (config, settings) = load_settings()?;
+    let verbose = settings.verbose.unwrap_or_default();
+
+    let options = Options {
         verbose,
-        dir_as_keyword,
-    } = Args::parse();
+        dry_run: args.dry_run,
+        on_conflict: settings.on_conflict.unwrap_or_default(),
+        mode: settings.mode.unwrap_or_default(),
+        keep_going: settings.keep_going.unwrap_or_default(),
+        recursive: settings.recursive.unwrap_or_default(),
+        max_depth: settings.max_depth,
+        prune_empty: settings.prune_empty.unwrap_or_default(),
+        include_hidden: settings.include_hidden.unwrap_or_default(),
+        symlinks: match (settings.follow_symlinks, settings.skip_symlinks) {
+            (Some(true), _) => Symlinks::Follow,
+            (_, Some(true)) => Symlinks::Skip,
+            _ => Symlinks::AsFiles,
+        },
+        non_utf8_names: settings.non_utf8_names.unwrap_or_default(),
+        filters: Filters::new(
+            &settings.include.unwrap_or_default(),
+            &settings.exclude.unwrap_or_default(),
+        )?,
+        match_mode: settings.match_mode.unwrap_or_default(),
+        normalizer: Normalizer {
+            form: settings.normalize.unwrap_or_default(),
+            fold_width: settings.fold_width.unwrap_or_default(),
+            fold_kana: settings.fold_kana.unwrap_or_default(),
+        },
+        segmentation: settings.segment.unwrap_or_default(),
+        strategies: settings.strategy.unwrap_or_default(),
+        hide: settings.hide.unwrap_or_default(),
+        stopwords: match &settings.stopwords_file {
+            Some(path) => load_stopwords(path)?,
+            None => vec![],
+        },
+        priority: settings.priority.unwrap_or_default(),
+        all_matches: settings.all_matches,
+    };
 
-    let pathbuf = parse_path(path)?;
+    if let Some(by) = settings.by_extension {
+        let sniff = settings.sniff.unwrap_or_default();
+        let _ = by_extension::execute(pathbuf, by, sniff, &config.categories, &options)?;
+        return Ok(());
+    }
+
+    if let Some(template) = settings.by_date {
+        let template = DateTemplate::parse(&template)?;
+        let sources = settings.date_source.unwrap_or_default();
+        let undated_dir = settings.undated_dir.as_deref();
+        let _ = by_date::execute(pathbuf, &template, &sources, undated_dir, &options)?;
+        return Ok(());
+    }
 
-    if let Some(keywords) = keywords {
-        return use_keywords(keywords, pathbuf, verbose);
+    if let Some(buckets) = settings.by_size {
+        let buckets = parse_buckets(&buckets)?;
+        let _ = by_size::execute(pathbuf, &buckets, &options)?;
+        return Ok(());
     }
 
-    if dir_as_keyword {
-        return use_dirs_as_keywords(pathbuf, verbose);
+    // `-k` is the first level.
+    let levels = settings
+        .keywords
+        .into_iter()
+        .chain(settings.levels.unwrap_or_default())
+        .collect::<Vec<_>>();
+    if !levels.is_empty() {
+        return use_keywords(levels, pathbuf, &options, &config.aliases);
     }
 
-    interactive_mode(pathbuf, verbose)
+    if settings.dir_as_keyword.unwrap_or_default() {
+        return use_dirs_as_keywords(pathbuf, &options);
+    }
+
+    interactive_mode(pathbuf, &options, &config.aliases)
 }
 
-fn use_keywords(keywords: String, pathbuf: PathBuf, verbose: bool) -> Result<(), Error> {
-    let keywords = parse_args(keywords)?;
+fn use_keywords(
+    levels: Vec<String>,
+    pathbuf: PathBuf,
+    options: &Options,
+    aliases: &BTreeMap<String, Vec<String>>,
+) -> Result<(), Error> {
+    let levels = levels
+        .into_iter()
+        .map(|keywords| apply_aliases(parse_args(keywords)?, aliases))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    move_files_to_dir_by_keywords(keywords, pathbuf, verbose)
+    move_files_to_dir_by_keywords(levels, pathbuf, options)
 }
 
-fn interactive_mode(pathbuf: PathBuf, verbose: bool) -> Result<(), Error> {
-    let keywords = interactive::execute(&pathbuf, tui::run)?;
+fn interactive_mode(
+    pathbuf: PathBuf,
+    options: &Options,
+    aliases: &BTreeMap<String, Vec<String>>,
+) -> Result<(), Error> {
+    let levels = interactive::execute(&pathbuf, options, tui::run)?
+        .into_iter()
+        .map(|keywords| apply_aliases(keywords_to_rules(keywords), aliases))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    move_files_to_dir_by_keywords(levels, pathbuf, options)
+}
 
-    move_files_to_dir_by_keywords(keywords, pathbuf, verbose)
+fn use_dirs_as_keywords(pathbuf: PathBuf, options: &Options) -> Result<(), Error> {
+    let _ = dirs_as_keywords::execute(pathbuf, options)?;
+    Ok(())
 }
 
-fn use_dirs_as_keywords(pathbuf: PathBuf, verbose: bool) -> Result<(), Error> {
-    let _ = dirs_as_keywords::execute(pathbuf, verbose)?;
+fn undo_last_run(pathbuf: PathBuf, verbose: bool, dry_run: bool) -> Result<(), Error> {
+    let result = journal::undo(&pathbuf, verbose, dry_run)?;
+    if !dry_run {
+        print_undo_result(Ok(result));
+    }
     Ok(())
 }
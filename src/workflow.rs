@@ -1,2 +1,5 @@
+pub mod by_date;
+pub mod by_extension;
+pub mod by_size;
 pub mod dirs_as_keywords;
 pub mod interactive;
@@ -0,0 +1,146 @@
+use crate::libs::date::{file_date, DateTemplate};
+use crate::libs::errors::Error;
+use crate::libs::fs::{files_in_dir, plan_grouping, run_plan};
+use crate::libs::options::{DateSource, Options};
+use crate::libs::plan::Plan;
+use crate::libs::rule::is_relative_dir;
+
+use std::path::{Path, PathBuf};
+
+/// Plans grouping files into directories built from their dates with `template`.
+/// Files without a date go into `undated_dir`, or are left in place and listed.
+pub fn plan(
+    pathbuf: &Path,
+    template: &DateTemplate,
+    sources: &[DateSource],
+    undated_dir: Option<&str>,
+    options: &Options,
+) -> Result<Plan, Error> {
+    if let Some(dir) = undated_dir.filter(|dir| !is_relative_dir(dir)) {
+        return Err(Error::InvalidDateTemplateError(format!(
+            "{}: undated directory must be a relative path",
+            dir
+        )));
+    }
+    let skip_dirs = undated_dir
+        .and_then(|dir| dir.split('/').next())
+        .map(|top| top.to_string())
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let files = files_in_dir(pathbuf, options, &skip_dirs)?;
+    let mut undated = vec![];
+    let mut plan = plan_grouping(pathbuf, &files, options, |path| {
+        match (file_date(&pathbuf.join(path), sources), undated_dir) {
+            (Some(date), _) => Some(template.render(date)),
+            (None, Some(dir)) => Some(dir.to_string()),
+            (None, None) => {
+                undated.push(path.to_string_lossy().to_string());
+                None
+            }
+        }
+    })?;
+    plan.undated = undated;
+
+    Ok(plan)
+}
+
+pub fn execute(
+    pathbuf: PathBuf,
+    template: &DateTemplate,
+    sources: &[DateSource],
+    undated_dir: Option<&str>,
+    options: &Options,
+) -> Result<Vec<String>, Error> {
+    let plan = plan(&pathbuf, template, sources, undated_dir, options)?;
+
+    run_plan(&pathbuf, &plan, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::journal;
+    use std::fs;
+
+    #[test]
+    fn test_by_date_plan() {
+        let tmpdir = std::env::temp_dir().join("test_by_date_plan");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::write(tmpdir.join("inquiry_2021-01-01.txt"), b"").unwrap();
+        fs::write(tmpdir.join("invoice_2021-05.pdf"), b"").unwrap();
+        fs::write(tmpdir.join("notes.txt"), b"").unwrap();
+
+        let template = DateTemplate::parse("{yyyy}-Q{q}").unwrap();
+        let options = Options::default();
+        let plan = plan(&tmpdir, &template, &[DateSource::Filename], None, &options).unwrap();
+
+        let mut dsts = plan
+            .transfers
+            .iter()
+            .map(|t| t.dst.strip_prefix(&tmpdir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        dsts.sort();
+        assert_eq!(
+            dsts,
+            vec![
+                Path::new("2021-Q1").join("inquiry_2021-01-01.txt"),
+                Path::new("2021-Q2").join("invoice_2021-05.pdf"),
+            ]
+        );
+        assert_eq!(plan.undated, vec!["notes.txt"]);
+    }
+
+    #[test]
+    fn test_by_date_execute() {
+        let tmpdir = std::env::temp_dir().join("test_by_date_execute");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::write(tmpdir.join("inquiry_2021-01-01.txt"), b"").unwrap();
+        fs::write(tmpdir.join("notes.txt"), b"").unwrap();
+        fs::create_dir_all(tmpdir.join("2020").join("12")).unwrap();
+        fs::write(
+            tmpdir.join("2020").join("12").join("memo_2020-12-24.txt"),
+            b"",
+        )
+        .unwrap();
+
+        let template = DateTemplate::parse("{yyyy}/{mm}").unwrap();
+        let options = Options {
+            recursive: true,
+            ..Default::default()
+        };
+        let moved = execute(
+            tmpdir.clone(),
+            &template,
+            &[DateSource::Filename],
+            Some("undated"),
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(moved.len(), 2);
+        assert!(tmpdir
+            .join("2021")
+            .join("01")
+            .join("inquiry_2021-01-01.txt")
+            .exists());
+        assert!(tmpdir.join("undated").join("notes.txt").exists());
+        assert!(tmpdir
+            .join("2020")
+            .join("12")
+            .join("memo_2020-12-24.txt")
+            .exists());
+
+        journal::undo(&tmpdir, false, false).unwrap();
+        assert!(tmpdir.join("inquiry_2021-01-01.txt").exists());
+        assert!(tmpdir.join("notes.txt").exists());
+    }
+}
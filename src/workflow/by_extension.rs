@@ -0,0 +1,138 @@
+use crate::libs::errors::Error;
+use crate::libs::file_type::{extension_dir, Category};
+use crate::libs::fs::{files_in_dir, plan_grouping, run_plan};
+use crate::libs::options::{ByExtension, Options};
+use crate::libs::plan::Plan;
+use crate::libs::rule::is_relative_dir;
+use crate::libs::stdout::skipped_entry;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Plans grouping files into directories named after their extensions or categories.
+/// `categories` rename the category directories.
+pub fn plan(
+    pathbuf: &Path,
+    by: ByExtension,
+    sniff: bool,
+    categories: &BTreeMap<Category, String>,
+    options: &Options,
+) -> Result<Plan, Error> {
+    if let Some(name) = categories.values().find(|name| !is_relative_dir(name)) {
+        return Err(Error::ConfigError(format!(
+            "category directory {} must be a relative path",
+            name
+        )));
+    }
+    // extension directories are only known after the scan, files in them are skipped below.
+    let skip_dirs = match by {
+        ByExtension::Extension => vec![],
+        ByExtension::Category => Category::all()
+            .map(|category| category.dir(categories))
+            .filter_map(|dir| dir.split('/').next().map(|top| top.to_string()))
+            .collect(),
+    };
+
+    let files = files_in_dir(pathbuf, options, &skip_dirs)?;
+    plan_grouping(pathbuf, &files, options, |path| {
+        let dirname = extension_dir(&pathbuf.join(path), by, sniff, categories);
+        if dirname.is_none() && options.verbose {
+            skipped_entry(path.display().to_string(), "unknown file type".to_string());
+        }
+        dirname
+    })
+}
+
+pub fn execute(
+    pathbuf: PathBuf,
+    by: ByExtension,
+    sniff: bool,
+    categories: &BTreeMap<Category, String>,
+    options: &Options,
+) -> Result<Vec<String>, Error> {
+    let plan = plan(&pathbuf, by, sniff, categories, options)?;
+
+    run_plan(&pathbuf, &plan, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::journal;
+    use std::fs;
+
+    #[test]
+    fn test_by_extension_execute() {
+        let tmpdir = std::env::temp_dir().join("test_by_extension");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::write(tmpdir.join("invoice.pdf"), b"").unwrap();
+        fs::write(tmpdir.join("pdf_manual.TXT"), b"").unwrap();
+        fs::write(tmpdir.join("README"), b"").unwrap();
+        fs::create_dir(tmpdir.join("pdf")).unwrap();
+        fs::write(tmpdir.join("pdf").join("grouped.pdf"), b"").unwrap();
+
+        let options = Options {
+            recursive: true,
+            ..Default::default()
+        };
+        let categories = BTreeMap::new();
+        let moved = execute(
+            tmpdir.clone(),
+            ByExtension::Extension,
+            false,
+            &categories,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(moved.len(), 2);
+        assert!(tmpdir.join("pdf").join("invoice.pdf").exists());
+        assert!(tmpdir.join("txt").join("pdf_manual.TXT").exists());
+        assert!(tmpdir.join("pdf").join("grouped.pdf").exists());
+        assert!(tmpdir.join("README").exists());
+
+        journal::undo(&tmpdir, false, false).unwrap();
+        assert!(tmpdir.join("invoice.pdf").exists());
+        assert!(tmpdir.join("pdf_manual.TXT").exists());
+    }
+
+    #[test]
+    fn test_by_category_execute() {
+        let tmpdir = std::env::temp_dir().join("test_by_category");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::write(tmpdir.join("photo.JPG"), b"").unwrap();
+        fs::write(tmpdir.join("scan"), b"%PDF-1.4\n").unwrap();
+        fs::write(tmpdir.join("backup.zip"), b"").unwrap();
+        fs::write(tmpdir.join("unknown.xyz"), b"").unwrap();
+
+        let options = Options::default();
+        let categories = BTreeMap::from([(Category::Images, "Pictures".to_string())]);
+        execute(
+            tmpdir.clone(),
+            ByExtension::Category,
+            true,
+            &categories,
+            &options,
+        )
+        .unwrap();
+
+        assert!(tmpdir.join("Pictures").join("photo.JPG").exists());
+        assert!(tmpdir.join("documents").join("scan").exists());
+        assert!(tmpdir.join("archives").join("backup.zip").exists());
+        assert!(tmpdir.join("unknown.xyz").exists());
+
+        let categories = BTreeMap::from([(Category::Images, "../Pictures".to_string())]);
+        assert!(matches!(
+            plan(&tmpdir, ByExtension::Category, false, &categories, &options),
+            Err(Error::ConfigError(_))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::libs::errors::Error;
+use crate::libs::fs::{files_with_metadata_in_dir, plan_grouping, run_plan};
+use crate::libs::options::Options;
+use crate::libs::plan::Plan;
+use crate::libs::size::{bucket_dir, SizeBucket};
+use crate::libs::stdout::skipped_entry;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Plans grouping files into the directories of the size buckets they fall into.
+/// A symlink grouped as a file is sized by its target, a broken one is left in place.
+pub fn plan(pathbuf: &Path, buckets: &[SizeBucket], options: &Options) -> Result<Plan, Error> {
+    let skip_dirs = buckets
+        .iter()
+        .filter_map(|bucket| bucket.name.split('/').next())
+        .map(|top| top.to_string())
+        .collect::<Vec<_>>();
+    let files = files_with_metadata_in_dir(pathbuf, options, &skip_dirs)?;
+    let sizes = files
+        .iter()
+        .map(|(path, metadata)| (path.as_path(), metadata))
+        .collect::<HashMap<_, _>>();
+    let paths = files.iter().map(|(path, _)| path).collect::<Vec<_>>();
+
+    plan_grouping(pathbuf, &paths, options, |path| {
+        let metadata = sizes[path];
+        // the scan keeps the metadata of a link grouped as a file, not of its target.
+        let len = if metadata.is_symlink() {
+            fs::metadata(pathbuf.join(path)).ok()?.len()
+        } else {
+            metadata.len()
+        };
+        let dirname = bucket_dir(buckets, len);
+        if dirname.is_none() && options.verbose {
+            skipped_entry(path.display().to_string(), "no size bucket".to_string());
+        }
+        dirname.map(|dirname| dirname.to_string())
+    })
+}
+
+pub fn execute(
+    pathbuf: PathBuf,
+    buckets: &[SizeBucket],
+    options: &Options,
+) -> Result<Vec<String>, Error> {
+    let plan = plan(&pathbuf, buckets, options)?;
+
+    run_plan(&pathbuf, &plan, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::journal;
+    use crate::libs::size::parse_buckets;
+    use std::fs;
+
+    #[test]
+    fn test_by_size_execute() {
+        let tmpdir = std::env::temp_dir().join("test_by_size");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::write(tmpdir.join("notes.txt"), vec![0; 10]).unwrap();
+        fs::write(tmpdir.join("photo.jpg"), vec![0; 2048]).unwrap();
+        fs::write(tmpdir.join("installer.dmg"), vec![0; 8192]).unwrap();
+        fs::create_dir(tmpdir.join("small")).unwrap();
+        fs::write(tmpdir.join("small").join("memo.txt"), vec![0; 10]).unwrap();
+
+        let buckets = parse_buckets("small=<1K,medium=1K-4K,large=>4K").unwrap();
+        let options = Options {
+            recursive: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        let moved = execute(tmpdir.clone(), &buckets, &options).unwrap();
+        assert!(moved.is_empty());
+        assert!(!tmpdir.join("medium").exists());
+        assert!(tmpdir.join("photo.jpg").exists());
+
+        let options = Options {
+            recursive: true,
+            ..Default::default()
+        };
+        let moved = execute(tmpdir.clone(), &buckets, &options).unwrap();
+        assert_eq!(moved.len(), 3);
+        assert!(tmpdir.join("small").join("notes.txt").exists());
+        assert!(tmpdir.join("medium").join("photo.jpg").exists());
+        assert!(tmpdir.join("large").join("installer.dmg").exists());
+        assert!(tmpdir.join("small").join("memo.txt").exists());
+
+        journal::undo(&tmpdir, false, false).unwrap();
+        assert!(tmpdir.join("notes.txt").exists());
+        assert!(tmpdir.join("photo.jpg").exists());
+        assert!(tmpdir.join("installer.dmg").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_by_size_sizes_links_by_target() {
+        let tmpdir = std::env::temp_dir().join("test_by_size_links");
+        if tmpdir.exists() {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::write(tmpdir.join("installer.dmg"), vec![0; 8192]).unwrap();
+        std::os::unix::fs::symlink(tmpdir.join("installer.dmg"), tmpdir.join("latest.dmg"))
+            .unwrap();
+        std::os::unix::fs::symlink(tmpdir.join("missing.dmg"), tmpdir.join("broken.dmg")).unwrap();
+
+        let buckets = parse_buckets("small=<1K,large=>4K").unwrap();
+        let plan = plan(&tmpdir, &buckets, &Options::default()).unwrap();
+        let mut dsts = plan
+            .transfers
+            .iter()
+            .map(|t| t.dst.strip_prefix(&tmpdir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        dsts.sort();
+        assert_eq!(
+            dsts,
+            vec![
+                Path::new("large").join("installer.dmg"),
+                Path::new("large").join("latest.dmg"),
+            ]
+        );
+    }
+}
@@ -1,84 +1,86 @@
+use crate::libs::dir_rules::load_dir_rules;
 use crate::libs::errors::Error;
-use crate::libs::fs::{dirs_in_dir, files_in_dir};
+use crate::libs::fs::{
+    choose_dir, count_matched_files, dirs_in_dir, files_in_dir, filter_files, plan_links,
+    plan_pruning, plan_transfer, run_plan,
+};
 use crate::libs::keywords::extract_keywords;
-use crate::libs::stdout::*;
+use crate::libs::options::Options;
+use crate::libs::plan::{Candidate, Plan};
 
-use regex::Regex;
-use std::collections::HashSet;
-use std::fs;
 use std::path::PathBuf;
 
-pub fn execute(pathbuf: PathBuf, verbose: bool) -> Result<Vec<String>, Error> {
-    let mut moved_files = vec![];
-    let mut update_dirs: HashSet<String> = HashSet::new();
+pub fn plan(pathbuf: &PathBuf, options: &Options) -> Result<Plan, Error> {
+    let mut plan = Plan::default();
 
-    let dirnames = dirs_in_dir(&pathbuf)?;
+    let dirnames = dirs_in_dir(pathbuf)?;
 
     if dirnames.is_empty() {
         return Err(Error::NoKeywordsFound);
     }
 
-    let dir_with_keyword_regexen: Vec<(String, Vec<Regex>)> = dirnames
+    let dir_with_rules = dirnames
         .iter()
         .map(|dirname| {
-            let keyword_regexen: Vec<Regex> = extract_keywords(dirname)
-                .into_iter()
-                .filter(|k| k.len() > 1)
-                .map(|k| {
-                    let re =
-                        regex::Regex::new(&format!(r"[\(\[\{{\-_, ]?{k}[\)\]\}}\-_, ]?")).unwrap();
-                    re
+            let rules = load_dir_rules(&pathbuf.join(dirname))?;
+            Ok((dirname.to_string(), extract_keywords(dirname), rules))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let files = files_in_dir(pathbuf, options, &dirnames)?;
+    let filenames = filter_files(&files, options);
+
+    let matcher = options.matcher();
+    let matches = filenames
+        .iter()
+        .map(|path| {
+            let candidates = dir_with_rules
+                .iter()
+                .filter_map(|(dirname, keywords, rules)| {
+                    let keyword_len = rules.match_len(keywords, path, &matcher)?;
+                    Some(Candidate {
+                        dir: dirname.to_string(),
+                        keyword_len,
+                    })
                 })
-                .collect();
-            (dirname.to_string(), keyword_regexen)
+                .collect::<Vec<_>>();
+            (path, candidates)
         })
-        .collect();
-
-    let filenames = files_in_dir(&pathbuf)?;
-
-    for filename in filenames.iter() {
-        for (dirname, keyword_regexen) in dir_with_keyword_regexen.iter() {
-            for re in keyword_regexen.iter() {
-                if re.is_match(filename) {
-                    let src = pathbuf.join(filename);
-                    if !src.exists() {
-                        already_moved(filename.to_string());
-                        continue;
-                    }
-
-                    let dst = pathbuf.join(dirname).join(filename);
-                    if dst.exists() {
-                        already_exists(filename.to_string());
-                        continue;
-                    }
-
-                    let result = fs::rename(&src, &dst);
-
-                    if result.is_ok() {
-                        update_dirs.insert(dirname.to_string());
-                        let dst_string = dst.to_str().unwrap().to_string();
-                        if verbose {
-                            moved(filename.to_string(), dst_string.clone());
-                        }
-                        moved_files.push(dst_string);
-                    } else {
-                        error(format!("src {}\ndst {}\n", src.display(), dst.display()));
-                        return Err(Error::MoveFileError(result.err().unwrap().to_string()));
-                    }
-                }
+        .collect::<Vec<_>>();
+    let matched_files = count_matched_files(matches.iter().map(|(_, c)| c.as_slice()));
+
+    for (path, candidates) in matches {
+        let filename = path.to_string_lossy();
+        if let Some(dirname) =
+            choose_dir(&mut plan, &filename, &candidates, &matched_files, options)?
+        {
+            let src = pathbuf.join(path);
+            let dst = pathbuf.join(&dirname).join(path.file_name().unwrap());
+            if plan_transfer(&mut plan, &filename, src.clone(), dst, options)? {
+                let dirs = candidates.iter().map(|c| c.dir.clone()).collect::<Vec<_>>();
+                plan_links(
+                    &mut plan, pathbuf, &filename, &src, &dirname, &dirs, options,
+                );
             }
         }
     }
 
-    print_result(update_dirs.len(), Ok(moved_files.clone()));
+    plan_pruning(pathbuf, &mut plan, options);
+
+    Ok(plan)
+}
+
+pub fn execute(pathbuf: PathBuf, options: &Options) -> Result<Vec<String>, Error> {
+    let plan = plan(&pathbuf, options)?;
 
-    Ok(moved_files)
+    run_plan(&pathbuf, &plan, options)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
+    use crate::libs::dir_rules::{ALIASES_FILENAME, RULES_FILENAME};
+    use std::fs::{self, File};
     use std::path::Path;
 
     #[test]
@@ -96,7 +98,7 @@ mod tests {
         File::create(tmpdir.join("invoice_2021-01-01.txt")).unwrap();
         File::create(tmpdir.join("questionnaire_2021-01-01.txt")).unwrap();
 
-        let result = execute(tmpdir.clone(), false);
+        let result = execute(tmpdir.clone(), &Options::default());
 
         assert!(result.is_ok());
         let result = result.unwrap();
@@ -106,13 +108,97 @@ mod tests {
                 .map(|x| {
                     let p = Path::new(x);
                     let f = p.file_name().unwrap();
-                    return f.to_str().unwrap();
+                    f.to_str().unwrap()
                 })
                 .collect::<Vec<_>>(),
             vec!["inquiry_2021-01-01.txt", "invoice_2021-01-01.txt"]
         );
-        assert_eq!(tmpdir.join("inquiry_2021-01-01.txt").exists(), false);
-        assert_eq!(tmpdir.join("invoice_2021-01-01.txt").exists(), false);
+        assert!(!tmpdir.join("inquiry_2021-01-01.txt").exists());
+        assert!(!tmpdir.join("invoice_2021-01-01.txt").exists());
         assert!(tmpdir.join("questionnaire_2021-01-01.txt").exists());
     }
+
+    #[test]
+    fn test_dirs_as_keywords_with_aliases() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_dirs_as_keywords_with_aliases");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::create_dir(tmpdir.join("Invoices")).unwrap();
+        fs::write(
+            tmpdir.join("Invoices").join(ALIASES_FILENAME),
+            "# spellings\ninv\n請求書\n",
+        )
+        .unwrap();
+        File::create(tmpdir.join("inv_2021-01-01.txt")).unwrap();
+        File::create(tmpdir.join("請求書_2021-01-01.txt")).unwrap();
+        File::create(tmpdir.join("inventory_2021-01-01.txt")).unwrap();
+
+        let plan = plan(&tmpdir, &Options::default()).unwrap();
+        let mut filenames = plan
+            .transfers
+            .iter()
+            .map(|t| t.filename.to_string())
+            .collect::<Vec<_>>();
+        filenames.sort();
+        assert_eq!(
+            filenames,
+            vec!["inv_2021-01-01.txt", "請求書_2021-01-01.txt"]
+        );
+
+        // keywords of the rule file are added to the aliases.
+        fs::write(
+            tmpdir.join("Invoices").join(RULES_FILENAME),
+            "keywords = [\"inventory\"]\n",
+        )
+        .unwrap();
+        let filenames = super::plan(&tmpdir, &Options::default())
+            .unwrap()
+            .transfers
+            .iter()
+            .map(|t| t.filename.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(filenames.len(), 3);
+    }
+
+    #[test]
+    fn test_dirs_as_keywords_with_rule_files() {
+        let tmpdir = std::env::temp_dir();
+        let tmpdir = tmpdir.join("test_dirs_as_keywords_with_rule_files");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        fs::create_dir(tmpdir.join("scans")).unwrap();
+        fs::write(
+            tmpdir.join("scans").join(RULES_FILENAME),
+            "extensions = [\"pdf\"]\nexclude = [\"*draft*\"]\n",
+        )
+        .unwrap();
+        File::create(tmpdir.join("IMG_0001.pdf")).unwrap();
+        File::create(tmpdir.join("IMG_0002_draft.pdf")).unwrap();
+        File::create(tmpdir.join("IMG_0003.txt")).unwrap();
+
+        let filenames = plan(&tmpdir, &Options::default())
+            .unwrap()
+            .transfers
+            .iter()
+            .map(|t| t.filename.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(filenames, vec!["IMG_0001.pdf"]);
+
+        fs::write(
+            tmpdir.join("scans").join(RULES_FILENAME),
+            "extensions = [\"pdf\"]\nregexes = ['IMG_(']\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            plan(&tmpdir, &Options::default()),
+            Err(Error::RuleFileError(e)) if e.contains(&format!("{}:2:", RULES_FILENAME))
+        ));
+    }
 }
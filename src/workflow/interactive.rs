@@ -1,22 +1,32 @@
 use spinners::{Spinner, Spinners};
-use std::path::PathBuf;
+use std::path::Path;
 
 use crate::libs::errors::Error;
-use crate::libs::fs::files_in_dir;
+use crate::libs::fs::{file_name, files_in_dir, filter_files};
 use crate::libs::keywords::*;
+use crate::libs::options::Options;
+
+/// Returns selected keywords per level of nested directories.
+type SelectKeywords = fn(Vec<(String, usize)>) -> Result<Vec<Vec<String>>, Error>;
 
 pub fn execute(
-    pathbuf: &PathBuf,
-    run: fn(Vec<(String, usize)>) -> Result<Vec<String>, Error>,
-) -> Result<Vec<String>, Error> {
-    println!("");
+    pathbuf: &Path,
+    options: &Options,
+    run: SelectKeywords,
+) -> Result<Vec<Vec<String>>, Error> {
+    println!();
     let mut sp = Spinner::new(
         Spinners::CircleHalves,
         "Extracting keywords with 2 or more charaters from filenames".into(),
     );
 
-    let filenames = files_in_dir(&pathbuf)?;
-    let keyword_hash = extract_keywords_and_count_from_filenames(&filenames);
+    let files = files_in_dir(pathbuf, options, &[])?;
+    let filenames = filter_files(&files, options)
+        .iter()
+        .map(|path| file_name(path))
+        .collect::<Vec<_>>();
+    let keyword_hash = extract_keywords_and_count_from_filenames(&filenames, options);
+    let keyword_hash = remove_noise(keyword_hash, options);
     let keyword_vec = sort_by_count_and_keyword_length(keyword_hash);
 
     // filter keywords that appear more than once.
@@ -39,6 +49,7 @@ pub fn execute(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::libs::options::Noise;
     use std::fs::{self, File};
     use std::path::Path;
 
@@ -57,20 +68,63 @@ mod tests {
         File::create(tmpdir.join("invoice_2022-02-01.txt")).unwrap();
         File::create(tmpdir.join("questionnaire_2021-03-01.txt")).unwrap();
 
-        let tui_mock = |keywords: Vec<(String, usize)>| -> Result<Vec<String>, Error> {
-            Ok(keywords
+        let tui_mock = |keywords: Vec<(String, usize)>| -> Result<Vec<Vec<String>>, Error> {
+            Ok(vec![keywords
                 .iter()
                 .map(|(keyword, _)| keyword.clone())
-                .collect())
+                .collect()])
         };
 
-        let expect = vec!["01", "2021", "inquiry", "invoice", "2022", "02"];
-        let result = execute(&tmpdir, tui_mock).unwrap();
-        assert_eq!(
-            result
+        let expect = ["01", "2021", "inquiry", "invoice", "2022", "02"];
+        let result = execute(&tmpdir, &Options::default(), tui_mock).unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(result[0]
+            .iter()
+            .all(|keyword| expect.contains(&keyword.as_str())));
+    }
+
+    #[test]
+    fn test_interactive_execute_hide() {
+        let tmpdir = std::env::temp_dir().join("test_interactive_hide");
+        if Path::exists(&tmpdir) {
+            fs::remove_dir_all(&tmpdir).unwrap();
+        }
+        fs::create_dir(&tmpdir).unwrap();
+
+        File::create(tmpdir.join("inquiry_2021-01-01_final.txt")).unwrap();
+        File::create(tmpdir.join("inquiry_2022-01-01_v2.txt")).unwrap();
+        File::create(tmpdir.join("invoice_202102_scan_final.txt")).unwrap();
+        File::create(tmpdir.join("invoice_202202_scan_v2.txt")).unwrap();
+        File::create(tmpdir.join("invoice_Jan_receipt.txt")).unwrap();
+        File::create(tmpdir.join("inquiry_Jan_receipt.txt")).unwrap();
+
+        let tui_mock = |keywords: Vec<(String, usize)>| -> Result<Vec<Vec<String>>, Error> {
+            Ok(vec![keywords
                 .iter()
-                .all(|keyword| expect.contains(&keyword.as_str())),
-            true
-        );
+                .map(|(keyword, _)| keyword.clone())
+                .collect()])
+        };
+
+        let options = Options {
+            hide: vec![
+                Noise::Stopwords,
+                Noise::Numeric,
+                Noise::Dates,
+                Noise::Versions,
+            ],
+            stopwords: vec!["Receipt".to_string()],
+            ..Default::default()
+        };
+        let mut result = execute(&tmpdir, &options, tui_mock).unwrap();
+        result[0].sort();
+        assert_eq!(result, vec![vec!["inquiry", "invoice"]]);
+
+        let options = Options {
+            hide: vec![Noise::Versions],
+            ..Default::default()
+        };
+        let result = execute(&tmpdir, &options, tui_mock).unwrap();
+        assert!(result[0].contains(&"final".to_string()));
+        assert!(!result[0].contains(&"v2".to_string()));
     }
 }